@@ -0,0 +1,303 @@
+use std::result::Result;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use name;
+use name::Name;
+
+pub struct Decoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    // reads may not go past this offset
+    limit: usize,
+}
+
+pub trait Decodable: Sized {
+    fn decode(d: &mut Decoder) -> DecodeResult<Self>;
+}
+
+pub fn decode<T: Decodable>(data: &[u8]) -> DecodeResult<T> {
+    let mut decoder = Decoder::new(data);
+    T::decode(&mut decoder)
+}
+
+pub type DecodeResult<T> = Result<T, DecodeError>;
+
+#[derive(Debug,PartialEq)]
+pub enum DecodeError {
+    /// `needed` more bytes were required at `offset` than the packet holds.
+    Truncated { offset: usize, needed: usize },
+    /// The label starting at `offset` uses a reserved length prefix.
+    BadLabel { offset: usize },
+    /// The compression pointer at `offset` refers to an invalid `target`,
+    /// either past the end of the packet or forward of the name.
+    BadPointer { offset: usize, target: usize },
+    /// The compression pointer at `offset` points back into its own name.
+    PointerLoop { offset: usize, target: usize },
+    /// The name starting at `offset` is longer than 255 octets.
+    NameTooLong { offset: usize },
+    /// The RDATA at `offset` does not fill exactly its RDLENGTH of `length`.
+    BadRdLength { offset: usize, length: usize },
+    /// The CAA property tag at `offset` is empty or not alphanumeric.
+    BadCaaTag { offset: usize },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::Truncated { offset, needed } =>
+                fmt.write_fmt(format_args!("truncated packet: {} bytes needed at offset {}", needed, offset)),
+            DecodeError::BadLabel { offset } =>
+                fmt.write_fmt(format_args!("invalid label at offset {}", offset)),
+            DecodeError::BadPointer { offset, target } =>
+                fmt.write_fmt(format_args!("invalid compression pointer at offset {} to {}", offset, target)),
+            DecodeError::PointerLoop { offset, target } =>
+                fmt.write_fmt(format_args!("compression pointer loop at offset {} to {}", offset, target)),
+            DecodeError::NameTooLong { offset } =>
+                fmt.write_fmt(format_args!("name at offset {} is longer than 255 octets", offset)),
+            DecodeError::BadRdLength { offset, length } =>
+                fmt.write_fmt(format_args!("RDATA does not match RDLENGTH {} at offset {}", length, offset)),
+            DecodeError::BadCaaTag { offset } =>
+                fmt.write_fmt(format_args!("invalid CAA tag at offset {}", offset)),
+        }
+    }
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(data: &'a [u8]) -> Decoder<'a> {
+        Decoder{
+            data: data,
+            position: 0,
+            limit: data.len(),
+        }
+    }
+
+    /// The whole input, including what lies before the position.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+    pub fn position(&self) -> usize {
+        self.position
+    }
+    /// Moves to `position`; a position past the limit makes the next read
+    /// fail as truncated.
+    pub fn seek(&mut self, position: usize) {
+        self.position = position;
+    }
+    /// Number of bytes left before the current limit.
+    pub fn remaining(&self) -> usize {
+        if self.position > self.limit {
+            return 0;
+        }
+        self.limit - self.position
+    }
+
+    fn ensure(&self, position: usize, needed: usize) -> DecodeResult<()> {
+        if position > self.limit || self.limit - position < needed {
+            return Err(DecodeError::Truncated { offset: position, needed: needed });
+        }
+        Ok(())
+    }
+
+    pub fn read_u8(&mut self) -> DecodeResult<u8> {
+        try!(self.ensure(self.position, 1));
+        let u = self.data[self.position];
+        self.position = self.position + 1;
+        Ok(u)
+    }
+    pub fn read_u16(&mut self) -> DecodeResult<u16> {
+        try!(self.ensure(self.position, 2));
+        let a = (self.data[self.position] as u16) << 8;
+        let b = self.data[self.position + 1] as u16;
+        self.position = self.position + 2;
+        Ok(a | b)
+    }
+    pub fn read_u32(&mut self) -> DecodeResult<u32> {
+        try!(self.ensure(self.position, 4));
+        let a = (self.data[self.position] as u32) << 24;
+        let b = (self.data[self.position + 1] as u32) << 16;
+        let c = (self.data[self.position + 2] as u32) << 8;
+        let d = self.data[self.position + 3] as u32;
+        self.position = self.position + 4;
+        Ok(a | b | c | d)
+    }
+    pub fn skip(&mut self, length: usize) -> DecodeResult<()> {
+        try!(self.ensure(self.position, length));
+        self.position = self.position + length;
+        Ok(())
+    }
+    pub fn read_bytes(&mut self, length: usize) -> DecodeResult<Vec<u8>> {
+        try!(self.ensure(self.position, length));
+        let bytes = self.data[self.position..self.position + length].to_vec();
+        self.position = self.position + length;
+        Ok(bytes)
+    }
+
+    /// Reads a <character-string>, a length octet and that many octets.
+    pub fn read_character_string(&mut self) -> DecodeResult<Vec<u8>> {
+        let length = try!(self.read_u8()) as usize;
+        self.read_bytes(length)
+    }
+
+    /// Reads a possibly compressed domain name.
+    pub fn read_name(&mut self) -> DecodeResult<Name> {
+        let start = self.position;
+        let mut labels = Vec::new();
+        try!(self.walk_name(|label| labels.push(label.to_vec())));
+        Name::from_labels(labels).map_err(|_| DecodeError::NameTooLong { offset: start })
+    }
+    /// Moves past a name with the same checks as `read_name`, but without
+    /// building it.
+    pub fn skip_name(&mut self) -> DecodeResult<()> {
+        self.walk_name(|_| {})
+    }
+
+    /// Calls `f` with every label of the name at the current position. Every
+    /// compression pointer must jump strictly before the labels read so far,
+    /// which rules out loops, and the expanded name may not exceed 255
+    /// octets.
+    fn walk_name<F>(&mut self, mut f: F) -> DecodeResult<()> where F: FnMut(&'a [u8]) {
+        let data = self.data;
+        let start = self.position;
+        let mut pos = self.position;
+        // lowest offset of the labels read so far
+        let mut segment_start = self.position;
+        let mut jumped = false;
+        let mut length = 1;
+        loop {
+            try!(self.ensure(pos, 1));
+            let len = data[pos];
+            if len & 0xc0 == 0xc0 {
+                try!(self.ensure(pos, 2));
+                let offset = pos;
+                let target = ((((len & 0x3f) as u16) << 8) | data[pos + 1] as u16) as usize;
+                pos = pos + 2;
+                if !jumped {
+                    self.position = pos;
+                    jumped = true;
+                }
+                if target >= segment_start && target <= offset {
+                    return Err(DecodeError::PointerLoop { offset: offset, target: target });
+                }
+                if target >= segment_start {
+                    return Err(DecodeError::BadPointer { offset: offset, target: target });
+                }
+                pos = target;
+                segment_start = target;
+                continue;
+            }
+            if len & 0xc0 != 0 {
+                // 0x40 and 0x80 are reserved label types
+                return Err(DecodeError::BadLabel { offset: pos });
+            }
+            pos = pos + 1;
+            if len == 0 {
+                break;
+            }
+            length = length + len as usize + 1;
+            if length > name::MAX_NAME_LENGTH {
+                return Err(DecodeError::NameTooLong { offset: start });
+            }
+            try!(self.ensure(pos, len as usize));
+            f(&data[pos..pos + len as usize]);
+            pos = pos + len as usize;
+        }
+        if !jumped {
+            self.position = pos;
+        }
+        Ok(())
+    }
+
+    /// Runs `f` with reads restricted to the next `length` bytes, which it
+    /// has to consume exactly. Names may still point back before them.
+    pub fn read_bounded<T, F>(&mut self, length: usize, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T> {
+        try!(self.ensure(self.position, length));
+        let start = self.position;
+        let end = start + length;
+        let limit = self.limit;
+        self.limit = end;
+        let result = f(self);
+        self.limit = limit;
+        let value = try!(result);
+        if self.position != end {
+            return Err(DecodeError::BadRdLength { offset: start, length: length });
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Decoder, Decodable, DecodeResult, DecodeError};
+    struct Person {
+        name: Vec<u8>,
+        age: u32,
+    }
+    impl Decodable for Person {
+        fn decode(decoder: &mut Decoder) -> DecodeResult<Person> {
+            let name = try!(decoder.read_bytes(8));
+            let age = try!(decoder.read_u32());
+            Ok(Person{ name: name, age: age })
+        }
+    }
+    #[test]
+    fn test_read_integers() {
+        let data = [170u8, 170u8, 85u8, 0b11111111, 0b00001111, 0b11110000, 0b10101010];
+        let mut decoder = Decoder::new(&data);
+        assert_eq!(decoder.read_u8(), Ok(170u8));
+        assert_eq!(decoder.read_u16(), Ok(43605u16));
+        assert_eq!(decoder.read_u32(), Ok(0b11111111000011111111000010101010));
+        assert_eq!(decoder.read_u8(), Err(DecodeError::Truncated { offset: 7, needed: 1 }));
+    }
+
+    #[test]
+    fn test_read_name() {
+        let data = [2u8, 97, 98, 1, 99, 0, 1, 120, 0xc0, 0];
+        let mut decoder = Decoder::new(&data);
+        assert_eq!(decoder.read_name().map(|n| n.to_string()), Ok("ab.c.".to_string()));
+        assert_eq!(decoder.read_name().map(|n| n.to_string()), Ok("x.ab.c.".to_string()));
+        assert_eq!(decoder.position(), data.len());
+    }
+
+    #[test]
+    fn test_read_character_string() {
+        let data = [2u8, 97, 98, 0, 3, 99];
+        let mut decoder = Decoder::new(&data);
+        assert_eq!(decoder.read_character_string(), Ok(b"ab".to_vec()));
+        assert_eq!(decoder.read_character_string(), Ok(vec![]));
+        assert_eq!(decoder.read_character_string(), Err(DecodeError::Truncated { offset: 5, needed: 3 }));
+    }
+
+    #[test]
+    fn test_skip() {
+        let data = [2u8, 97, 98, 1, 99, 0, 1, 120, 0xc0, 0, 7];
+        let mut decoder = Decoder::new(&data);
+        assert_eq!(decoder.skip_name(), Ok(()));
+        assert_eq!(decoder.skip_name(), Ok(()));
+        assert_eq!(decoder.skip(1), Ok(()));
+        assert_eq!(decoder.skip(1), Err(DecodeError::Truncated { offset: 11, needed: 1 }));
+        decoder.seek(6);
+        assert_eq!(decoder.read_name().map(|n| n.to_string()), Ok("x.ab.c.".to_string()));
+        decoder.seek(8);
+        assert_eq!(decoder.skip_name(), Ok(()));
+        assert_eq!(decoder.position(), 10);
+    }
+
+    #[test]
+    fn test_read_bounded() {
+        let data = [0u8, 1, 2, 3, 4];
+        let mut decoder = Decoder::new(&data);
+        assert_eq!(decoder.read_bounded(2, |d| d.read_u16()), Ok(1u16));
+        assert_eq!(decoder.read_bounded(2, |d| d.read_u32()), Err(DecodeError::Truncated { offset: 2, needed: 4 }));
+        assert_eq!(decoder.remaining(), 3);
+        assert_eq!(decoder.read_bounded(3, |d| d.read_u16()), Err(DecodeError::BadRdLength { offset: 2, length: 3 }));
+    }
+
+    #[test]
+    fn test_decode() {
+        let data = [116u8, 97, 107, 97, 102, 117, 109, 105, 0, 0, 0, 26];
+        let person: Person = super::decode(&data).ok().unwrap();
+        assert_eq!(person.name, b"takafumi".to_vec());
+        assert_eq!(person.age, 26);
+    }
+}
@@ -1,7 +1,16 @@
 use std::result::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use name::Name;
+
+/// Largest offset a compression pointer can refer to (14 bits).
+const MAX_POINTER_OFFSET: usize = 0x3fff;
 
 pub struct Encoder<'a> {
     buffer: &'a mut Vec<u8>,
+    // offsets of the names already written, keyed by their labels
+    names: HashMap<Vec<Vec<u8>>, u16>,
 }
 
 pub trait Encodable {
@@ -17,12 +26,77 @@ pub fn encode<T: Encodable>(object: &T) -> EncodeResult<Vec<u8>> {
     Ok(buffer)
 }
 
-pub type EncodeResult<T> = Result<T, String>;
+pub type EncodeResult<T> = Result<T, EncodeError>;
+
+#[derive(Debug,PartialEq)]
+pub enum EncodeError {
+    /// A label of `length` octets; at most 63 are allowed.
+    LabelTooLong { name: String, length: usize },
+    /// A name of `length` octets in wire format; at most 255 are allowed.
+    NameTooLong { name: String, length: usize },
+    /// A name with an empty label, such as `a..b`.
+    EmptyLabel { name: String },
+    /// A malformed `\` escape in a name.
+    BadEscape { name: String },
+    /// A label that cannot be converted to an IDNA A-label.
+    BadIdn { name: String },
+    /// A CAA property tag that is empty or not alphanumeric.
+    BadCaaTag { tag: String },
+    /// TXT or SPF RDATA without any character-string.
+    EmptyTxt,
+    /// A character-string of `length` octets; at most 255 are allowed.
+    CharacterStringTooLong { length: usize },
+    /// RDATA of `length` octets, more than RDLENGTH can express.
+    RdataTooLong { length: usize },
+    /// A message of `length` octets, more than any transport can carry.
+    MessageTooLarge { length: usize },
+    /// A response code above 15 in a message without an OPT record to
+    /// carry its upper bits.
+    ExtendedResponseCodeWithoutOpt { code: u16 },
+    /// A section count in the header that disagrees with its records.
+    CountMismatch { section: &'static str, count: u16, records: usize },
+    /// Patching at `position`, which has not been written yet.
+    InvalidPosition(usize),
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match *self {
+            EncodeError::LabelTooLong { ref name, length } =>
+                fmt.write_fmt(format_args!("label of {} octets in {} (at most 63)", length, name)),
+            EncodeError::NameTooLong { ref name, length } =>
+                fmt.write_fmt(format_args!("{} is {} octets long (at most 255)", name, length)),
+            EncodeError::EmptyLabel { ref name } =>
+                fmt.write_fmt(format_args!("empty label in {}", name)),
+            EncodeError::BadEscape { ref name } =>
+                fmt.write_fmt(format_args!("invalid escape in {}", name)),
+            EncodeError::BadIdn { ref name } =>
+                fmt.write_fmt(format_args!("invalid internationalized name {}", name)),
+            EncodeError::BadCaaTag { ref tag } =>
+                fmt.write_fmt(format_args!("invalid CAA tag {}", tag)),
+            EncodeError::EmptyTxt =>
+                fmt.write_str("TXT RDATA needs at least one character-string"),
+            EncodeError::CharacterStringTooLong { length } =>
+                fmt.write_fmt(format_args!("character-string of {} octets is too long (at most 255)", length)),
+            EncodeError::RdataTooLong { length } =>
+                fmt.write_fmt(format_args!("RDATA of {} octets is too long", length)),
+            EncodeError::MessageTooLarge { length } =>
+                fmt.write_fmt(format_args!("message of {} octets is too large", length)),
+            EncodeError::ExtendedResponseCodeWithoutOpt { code } =>
+                fmt.write_fmt(format_args!("response code {} needs an OPT record", code)),
+            EncodeError::CountMismatch { section, count, records } =>
+                fmt.write_fmt(format_args!("{} count is {} but there are {} records", section, count, records)),
+            EncodeError::InvalidPosition(position) =>
+                fmt.write_fmt(format_args!("cannot patch at {}", position)),
+        }
+    }
+}
 
 impl<'a> Encoder<'a> {
     pub fn new(buffer: &'a mut Vec<u8>) -> Encoder<'a> {
         Encoder{
             buffer: buffer,
+            names: HashMap::new(),
         }
     }
     pub fn emit_u8(&mut self, v: u8) -> EncodeResult<()> {
@@ -50,10 +124,65 @@ impl<'a> Encoder<'a> {
         self.buffer.extend(v.as_bytes());
         Ok(())
     }
+    pub fn emit_bytes(&mut self, v: &[u8]) -> EncodeResult<()> {
+        self.buffer.extend(v);
+        Ok(())
+    }
+
+    /// Writes a <character-string>: a length octet followed by up to 255
+    /// octets (RFC 1035 3.3).
+    pub fn emit_character_string(&mut self, v: &[u8]) -> EncodeResult<()> {
+        if v.len() > 0xff {
+            return Err(EncodeError::CharacterStringTooLong { length: v.len() });
+        }
+        try!(self.emit_u8(v.len() as u8));
+        self.emit_bytes(v)
+    }
+
+    /// Writes a domain name, replacing the longest suffix that has already
+    /// been written by a compression pointer (RFC 1035 4.1.4).
+    pub fn emit_name(&mut self, v: &Name) -> EncodeResult<()> {
+        let labels = v.labels();
+        for i in 0..labels.len() {
+            let suffix = labels[i..].to_vec();
+            if let Some(&offset) = self.names.get(&suffix) {
+                return self.emit_u16(0xc000 | offset);
+            }
+            let position = self.position();
+            if position <= MAX_POINTER_OFFSET {
+                self.names.insert(suffix, position as u16);
+            }
+            try!(self.emit_u8(labels[i].len() as u8));
+            try!(self.emit_bytes(&labels[i]));
+        }
+        self.emit_u8(0)
+    }
+
+    /// Writes a domain name without compression, as required for names in
+    /// the RDATA of newer types such as SRV (RFC 3597 4).
+    pub fn emit_name_uncompressed(&mut self, v: &Name) -> EncodeResult<()> {
+        for label in v.labels() {
+            try!(self.emit_u8(label.len() as u8));
+            try!(self.emit_bytes(label));
+        }
+        self.emit_u8(0)
+    }
+
+    pub fn position(&self) -> usize {
+        self.buffer.len()
+    }
+    pub fn patch_u16(&mut self, pos: usize, v: u16) -> EncodeResult<()> {
+        if pos + 2 > self.buffer.len() {
+            return Err(EncodeError::InvalidPosition(pos));
+        }
+        self.buffer[pos] = (v >> 8) as u8;
+        self.buffer[pos + 1] = (v & 0b11111111) as u8;
+        Ok(())
+    }
 
     pub fn emit_vec<T: Encodable>(&mut self, vs: &Vec<T>) -> EncodeResult<()> {
         for v in vs {
-            let _ = v.encode(self);
+            try!(v.encode(self));
         };
         Ok(())
     }
@@ -61,7 +190,9 @@ impl<'a> Encoder<'a> {
 
 #[cfg(test)]
 mod test {
-    use super::{Encoder, EncodeResult, Encodable};
+    use name::Name;
+    use super::{Encoder, EncodeResult, EncodeError, Encodable};
+    use std::str::FromStr;
     struct Person {
         name: &'static str,
         age: u32,
@@ -131,6 +262,112 @@ mod test {
         assert_eq!(encoder.buffer, &vec![104, 111, 103, 101]);
     }
 
+    #[test]
+    fn test_write_bytes() {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf);
+
+        let result = encoder.emit_bytes(&[0, 255, 7]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(encoder.buffer, &vec![0, 255, 7]);
+    }
+
+    #[test]
+    fn test_write_character_string() {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf);
+
+        assert_eq!(encoder.emit_character_string(b"ab"), Ok(()));
+        assert_eq!(encoder.emit_character_string(b""), Ok(()));
+        assert_eq!(encoder.emit_character_string(&[0; 256]), Err(EncodeError::CharacterStringTooLong { length: 256 }));
+        assert_eq!(encoder.buffer, &vec![2, 97, 98, 0]);
+    }
+
+
+    #[test]
+    fn test_write_name() {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf);
+
+        let result = encoder.emit_name(&Name::from_str("ab.c.").unwrap());
+        assert_eq!(result, Ok(()));
+        assert_eq!(encoder.buffer, &vec![2, 97, 98, 1, 99, 0]);
+    }
+
+    #[test]
+    fn test_write_escaped_name() {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf);
+
+        let result = encoder.emit_name(&Name::from_str("a\\.b\\032.c").unwrap());
+        assert_eq!(result, Ok(()));
+        assert_eq!(encoder.buffer, &vec![4, 97, 46, 98, 32, 1, 99, 0]);
+    }
+
+    #[test]
+    fn test_write_name_uncompressed() {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf);
+
+        let _ = encoder.emit_name(&Name::from_str("ab.c").unwrap());
+        let _ = encoder.emit_name_uncompressed(&Name::from_str("x.ab.c").unwrap());
+        let _ = encoder.emit_name(&Name::from_str("x.ab.c").unwrap());
+        assert_eq!(encoder.buffer, &vec![
+            2, 97, 98, 1, 99, 0,
+            1, 120, 2, 97, 98, 1, 99, 0,
+            1, 120, 0xc0, 0,
+        ]);
+    }
+
+    #[test]
+    fn test_write_name_compressed() {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf);
+
+        let _ = encoder.emit_u16(0);
+        let _ = encoder.emit_name(&Name::from_str("ab.c").unwrap());
+        let _ = encoder.emit_name(&Name::from_str("x.ab.c").unwrap());
+        let _ = encoder.emit_name(&Name::from_str("ab.c").unwrap());
+        let _ = encoder.emit_name(&Name::from_str("c").unwrap());
+        let _ = encoder.emit_name(&Name::from_str("AB.c").unwrap());
+        assert_eq!(encoder.buffer, &vec![
+            0, 0,
+            2, 97, 98, 1, 99, 0, // ab.c at 2
+            1, 120, 0xc0, 2,     // x + pointer to ab.c
+            0xc0, 2,             // pointer to ab.c
+            0xc0, 5,             // pointer to c
+            2, 65, 66, 0xc0, 5,  // AB keeps its case + pointer to c
+        ]);
+    }
+
+    struct Failing;
+    impl Encodable for Failing {
+        fn encode(&self, _: &mut Encoder) -> EncodeResult<()> {
+            Err(EncodeError::InvalidPosition(0))
+        }
+    }
+
+    #[test]
+    fn test_emit_vec_propagates_errors() {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf);
+
+        assert_eq!(encoder.emit_vec(&vec![Failing]), Err(EncodeError::InvalidPosition(0)));
+    }
+
+    #[test]
+    fn test_patch_u16() {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf);
+
+        let _ = encoder.emit_u16(0);
+        let _ = encoder.emit_u8(7);
+        let result = encoder.patch_u16(0, 43605u16);
+        assert_eq!(result, Ok(()));
+        assert_eq!(encoder.buffer, &vec![170u8, 85u8, 7u8]);
+        assert_eq!(encoder.patch_u16(2, 0), Err(EncodeError::InvalidPosition(2)));
+    }
+
     #[test]
     fn test_encode() {
         let person = Person{
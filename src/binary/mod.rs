@@ -1 +1,2 @@
 pub mod encoder;
+pub mod decoder;
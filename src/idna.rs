@@ -0,0 +1,332 @@
+use std::char;
+use std::str;
+use binary::encoder::EncodeError;
+use name::{self, Name};
+use unicode_idna;
+use icu_properties::CodePointMapData;
+use icu_properties::props::GeneralCategory;
+
+// Bootstring parameters for punycode (RFC 3492 5).
+const BASE: u32 = 36;
+const TMIN: u32 = 1;
+const TMAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 0x80;
+
+const ACE_PREFIX: &'static str = "xn--";
+
+macro_rules! some {
+    ($e:expr) => (match $e { Some(v) => v, None => return None })
+}
+
+/// Converts a name in presentation format to its ASCII form, replacing
+/// every label with non-ASCII characters by an A-label.
+///
+/// The ideographic and fullwidth dots separate labels. ASCII labels are
+/// left alone, escapes included. Other labels go through UTS #46
+/// nontransitional processing, with its mapping, NFC and the hyphen, bidi
+/// and joiner checks, and must then hold only what IDNA2008 allows: letters,
+/// marks and digits (RFC 5892 2.1). Symbols such as U+2603 are rejected
+/// with `BadIdn` even though UTS #46 accepts them.
+pub fn to_ascii(v: &str) -> Result<String, EncodeError> {
+    let mut labels = Vec::new();
+    let mut label = String::new();
+    let mut escaped = false;
+    for c in v.chars() {
+        if escaped {
+            label.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => {
+                label.push(c);
+                escaped = true;
+            },
+            '.' | '\u{3002}' | '\u{ff0e}' | '\u{ff61}' => {
+                labels.push(label);
+                label = String::new();
+            },
+            c => label.push(c),
+        }
+    }
+    labels.push(label);
+
+    let mut ascii = Vec::new();
+    for label in labels {
+        ascii.push(try!(label_to_ascii(&label, v)));
+    }
+    Ok(ascii.join("."))
+}
+
+fn label_to_ascii(label: &str, v: &str) -> Result<String, EncodeError> {
+    if label.is_ascii() {
+        return Ok(label.to_string());
+    }
+    let ascii = match unicode_idna::domain_to_ascii_strict(label) {
+        Ok(ascii) => ascii,
+        Err(_) => return Err(EncodeError::BadIdn { name: v.to_string() }),
+    };
+    // a mapping may not produce a label separator
+    if ascii.contains('.') {
+        return Err(EncodeError::BadIdn { name: v.to_string() });
+    }
+    if ascii.starts_with(ACE_PREFIX) {
+        let valid = match decode(&ascii[ACE_PREFIX.len()..]) {
+            Some(unicode) => unicode.chars().all(is_letter_digit),
+            None => false,
+        };
+        if !valid {
+            return Err(EncodeError::BadIdn { name: v.to_string() });
+        }
+    }
+    Ok(ascii)
+}
+
+/// Whether IDNA2008 can allow `c` in a U-label (RFC 5892 2.1 and 2.6). The
+/// joiners are included as UTS #46 has already checked their context.
+fn is_letter_digit(c: char) -> bool {
+    if c.is_ascii() {
+        return true;
+    }
+    match c as u32 {
+        0x200c | 0x200d | 0x06fd | 0x06fe | 0x0f0b | 0x3007 => return true,
+        _ => {},
+    }
+    match CodePointMapData::<GeneralCategory>::new().get(c) {
+        GeneralCategory::LowercaseLetter | GeneralCategory::UppercaseLetter |
+        GeneralCategory::OtherLetter | GeneralCategory::ModifierLetter |
+        GeneralCategory::NonspacingMark | GeneralCategory::SpacingMark |
+        GeneralCategory::DecimalNumber => true,
+        _ => false,
+    }
+}
+
+/// Presents `name` with its A-labels decoded to Unicode, or returns `None`
+/// when it has no valid A-labels.
+pub fn to_unicode(name: &Name) -> Option<String> {
+    let mut found = false;
+    let mut labels = Vec::new();
+    for label in name.labels() {
+        match label_to_unicode(label) {
+            Some(u) => {
+                found = true;
+                labels.push(u);
+            },
+            None => labels.push(name::to_presentation(&[label.clone()])),
+        }
+    }
+    if !found {
+        return None;
+    }
+    let mut unicode = labels.join(".");
+    if name.is_fqdn() {
+        unicode.push('.');
+    }
+    Some(unicode)
+}
+
+fn label_to_unicode(label: &[u8]) -> Option<String> {
+    if label.len() <= ACE_PREFIX.len() || !label[..ACE_PREFIX.len()].eq_ignore_ascii_case(ACE_PREFIX.as_bytes()) {
+        return None;
+    }
+    let encoded = some!(str::from_utf8(&label[ACE_PREFIX.len()..]).ok()).to_ascii_lowercase();
+    let decoded = some!(decode(&encoded));
+    // an A-label has to be the exact encoding of a non-ASCII label
+    let chars: Vec<char> = decoded.chars().collect();
+    if decoded.is_ascii() || encode(&chars) != Some(encoded) {
+        return None;
+    }
+    Some(decoded)
+}
+
+/// Encodes Unicode characters with punycode (RFC 3492 6.3), without the
+/// ACE prefix.
+pub fn encode(input: &[char]) -> Option<String> {
+    let mut output: String = input.iter().cloned().filter(|c| c.is_ascii()).collect();
+    let basic = output.len() as u32;
+    if basic > 0 {
+        output.push('-');
+    }
+    let mut n = INITIAL_N;
+    let mut delta: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut handled = basic;
+    while (handled as usize) < input.len() {
+        let m = some!(input.iter().map(|&c| c as u32).filter(|&c| c >= n).min());
+        delta = some!(delta.checked_add(some!((m - n).checked_mul(handled + 1))));
+        n = m;
+        for &c in input {
+            let c = c as u32;
+            if c < n {
+                delta = some!(delta.checked_add(1));
+            }
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = threshold(k, bias);
+                    if q < t {
+                        break;
+                    }
+                    output.push(digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k = k + BASE;
+                }
+                output.push(digit(q));
+                bias = adapt(delta, handled + 1, handled == basic);
+                delta = 0;
+                handled = handled + 1;
+            }
+        }
+        delta = some!(delta.checked_add(1));
+        n = n + 1;
+    }
+    Some(output)
+}
+
+/// Decodes a punycode string (RFC 3492 6.2), without the ACE prefix.
+pub fn decode(input: &str) -> Option<String> {
+    let (basic, extended) = match input.rfind('-') {
+        Some(i) => (&input[..i], &input[i + 1..]),
+        None => ("", input),
+    };
+    if !basic.is_ascii() {
+        return None;
+    }
+    let mut output: Vec<char> = basic.chars().collect();
+    let mut n = INITIAL_N;
+    let mut i: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut digits = extended.bytes();
+    loop {
+        let old_i = i;
+        let mut w: u32 = 1;
+        let mut k = BASE;
+        let mut first = true;
+        loop {
+            let d = match digits.next() {
+                Some(b) => some!(digit_value(b)),
+                None if first => return Some(output.into_iter().collect()),
+                None => return None,
+            };
+            first = false;
+            i = some!(i.checked_add(some!(d.checked_mul(w))));
+            let t = threshold(k, bias);
+            if d < t {
+                break;
+            }
+            w = some!(w.checked_mul(BASE - t));
+            k = k + BASE;
+        }
+        let length = output.len() as u32 + 1;
+        bias = adapt(i - old_i, length, old_i == 0);
+        n = some!(n.checked_add(i / length));
+        i = i % length;
+        output.insert(i as usize, some!(char::from_u32(n)));
+        i = i + 1;
+    }
+}
+
+fn threshold(k: u32, bias: u32) -> u32 {
+    if k <= bias {
+        TMIN
+    } else if k >= bias + TMAX {
+        TMAX
+    } else {
+        k - bias
+    }
+}
+
+fn adapt(delta: u32, length: u32, first: bool) -> u32 {
+    let mut delta = if first { delta / DAMP } else { delta / 2 };
+    delta = delta + delta / length;
+    let mut k = 0;
+    while delta > ((BASE - TMIN) * TMAX) / 2 {
+        delta = delta / (BASE - TMIN);
+        k = k + BASE;
+    }
+    k + (BASE - TMIN + 1) * delta / (delta + SKEW)
+}
+
+fn digit(d: u32) -> char {
+    if d < 26 {
+        (b'a' + d as u8) as char
+    } else {
+        (b'0' + (d - 26) as u8) as char
+    }
+}
+
+fn digit_value(b: u8) -> Option<u32> {
+    match b {
+        b'a'...b'z' => Some((b - b'a') as u32),
+        b'A'...b'Z' => Some((b - b'A') as u32),
+        b'0'...b'9' => Some((b - b'0') as u32 + 26),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encode, decode, to_ascii, to_unicode};
+    use binary::encoder::EncodeError;
+    use name::Name;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_punycode() {
+        let samples = [
+            ("bücher", "bcher-kva"),
+            ("münchen", "mnchen-3ya"),
+            ("例え", "r8jz45g"),
+            ("テスト", "zckzah"),
+            // RFC 3492 7.1 (L)
+            ("3年B組金八先生", "3B-ww4c5e180e575a65lsy2b"),
+        ];
+        for &(unicode, encoded) in samples.iter() {
+            let chars: Vec<char> = unicode.chars().collect();
+            assert_eq!(encode(&chars), Some(encoded.to_string()));
+            assert_eq!(decode(encoded), Some(unicode.to_string()));
+        }
+        assert_eq!(decode("abc-"), Some("abc".to_string()));
+        assert_eq!(decode("a-!"), None);
+        assert_eq!(decode("zzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzz"), None);
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        assert_eq!(to_ascii("例え.テスト"), Ok("xn--r8jz45g.xn--zckzah".to_string()));
+        assert_eq!(to_ascii("例え。テスト．"), Ok("xn--r8jz45g.xn--zckzah.".to_string()));
+        assert_eq!(to_ascii("Bücher.example"), Ok("xn--bcher-kva.example".to_string()));
+        assert_eq!(to_ascii("ＷＷＷ.example"), Ok("www.example".to_string()));
+        assert_eq!(to_ascii("WWW.a\\.b"), Ok("WWW.a\\.b".to_string()));
+        assert_eq!(to_ascii("."), Ok(".".to_string()));
+        assert_eq!(to_ascii("-ü.example"), Err(EncodeError::BadIdn { name: "-ü.example".to_string() }));
+        assert_eq!(to_ascii("a ü"), Err(EncodeError::BadIdn { name: "a ü".to_string() }));
+        // compatibility characters are mapped and NFD input is normalised
+        assert_eq!(to_ascii("ﬁx.com"), Ok("fix.com".to_string()));
+        assert_eq!(to_ascii("Ⅻ.com"), Ok("xii.com".to_string()));
+        assert_eq!(to_ascii("bu\u{308}cher.example"), Ok("xn--bcher-kva.example".to_string()));
+        // a virama is a mark, and a ZWJ is allowed after one
+        assert_eq!(to_ascii("हिन्दी.भारत"), Ok("xn--j2bd4cyah0f.xn--h2brj9c".to_string()));
+        assert_eq!(to_ascii("क\u{94d}\u{200d}.com"), Ok("xn--11b6iy14e.com".to_string()));
+        assert_eq!(to_ascii("a\u{200d}ü.com"), Err(EncodeError::BadIdn { name: "a\u{200d}ü.com".to_string() }));
+        // valid in UTS #46, but not in IDNA2008
+        assert_eq!(to_ascii("☃.example"), Err(EncodeError::BadIdn { name: "☃.example".to_string() }));
+    }
+
+    #[test]
+    fn test_to_unicode() {
+        let name = Name::from_str("www.XN--R8JZ45G.xn--zckzah.").unwrap();
+        assert_eq!(to_unicode(&name), Some("www.例え.テスト.".to_string()));
+        let name = Name::from_str("www.example").unwrap();
+        assert_eq!(to_unicode(&name), None);
+        // only ASCII, or not the encoding of what it decodes to
+        let name = Name::from_str("xn--abc-").unwrap();
+        assert_eq!(to_unicode(&name), None);
+        let name = Name::from_str("xn--bcher-kva8").unwrap();
+        assert_eq!(to_unicode(&name), None);
+    }
+}
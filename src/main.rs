@@ -1,18 +1,33 @@
 #![feature(convert)]
 extern crate regex;
 extern crate num;
+extern crate idna as unicode_idna;
+extern crate icu_properties;
 mod message;
+mod message_ref;
 mod resolver;
 mod resource;
 mod binary;
+mod name;
+mod idna;
 
 use resolver::Resolver;
-use resource::ResourceType;
+use resource::{ResourceType, ResourceClass};
+use name::Name;
 use std::env;
 use std::process::exit;
+use std::str::FromStr;
 
 fn usage() {
-    println!("usage: impact [name]");
+    println!("usage: impact [name] [type] [class]");
+}
+
+/// Shows `name` followed by its Unicode form when it has A-labels.
+fn display_name(name: &Name) -> String {
+    match idna::to_unicode(name) {
+        Some(unicode) => format!("{} ({})", name, unicode),
+        None => name.to_string(),
+    }
 }
 
 fn main() {
@@ -23,42 +38,56 @@ fn main() {
         exit(0);
     }
 
-    let name = args.next().unwrap();
-    let rtype = match args.next() {
-        Some(v) => match ResourceType::from_string(v) {
-            Some(t) => t,
-            None => ResourceType::A,
+    let name = match idna::to_ascii(&args.next().unwrap()).and_then(|v| Name::from_str(&v)) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("invalid name: {}", e);
+            exit(1);
         },
-        None => ResourceType::A,
     };
+    let mut rtype = ResourceType::A;
+    let mut rclass = ResourceClass::IN;
+    for arg in args {
+        match ResourceType::from_string(arg.clone()) {
+            Some(t) => rtype = t,
+            // types come first, so ANY is the QTYPE; CLASS255 gives the class
+            None => match ResourceClass::from_string(arg.clone()) {
+                Some(c) => rclass = c,
+                None => {
+                    println!("unknown type or class: {}", arg);
+                    exit(1);
+                },
+            },
+        }
+    }
 
     let resolver = Resolver::from_reolv_conf();
-    let response = resolver.resolve(name, rtype);
+    let response = resolver.resolve(name, rtype, rclass);
     match response {
         Ok(message) => {
             println!("Question: ");
             for q in message.question_record {
-                println!("{}	{:?}	{:?}", q.domain_name, q.query_class, q.query_type);
+                println!("{}	{}	{}", display_name(&q.domain_name), q.query_class, q.query_type);
             };
             println!("");
             if message.answer_pr_count > 0 {
                 println!("Answer: ");
                 for ans in message.answer_record {
-                    println!("{}	{}	{:?}	{:?}	{}", ans.name, ans.ttl, ans.rclass, ans.rtype, ans.rdata);
+                    println!("{}	{}	{}	{}	{}", display_name(&ans.name), ans.ttl, ans.rclass, ans.rtype, ans.rdata);
                 };
                 println!("");
             }
             if message.authorative_pr_count > 0 {
                 println!("Authority: ");
                 for ans in message.authorative_record {
-                    println!("{}	{}	{:?}	{:?}	{}", ans.name, ans.ttl, ans.rclass, ans.rtype, ans.rdata);
+                    println!("{}	{}	{}	{}	{}", display_name(&ans.name), ans.ttl, ans.rclass, ans.rtype, ans.rdata);
                 };
                 println!("");
             }
             if message.additional_pr_count > 0 {
                 println!("Additional: ");
                 for ans in message.additional_record {
-                    println!("{}	{}	{:?}	{:?}	{}", ans.name, ans.ttl, ans.rclass, ans.rtype, ans.rdata);
+                    println!("{}	{}	{}	{}	{}", display_name(&ans.name), ans.ttl, ans.rclass, ans.rtype, ans.rdata);
                 };
             }
         },
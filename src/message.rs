@@ -1,8 +1,14 @@
-use resource::{Resource, ResourceType, ResourceClass, RData, SOAData, MXData};
-use binary::encoder::{Encoder, EncodeResult, Encodable};
-use std::char;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use resource::{Resource, ResourceType, ResourceClass, RData, OPTData, EdnsOption};
+use binary::encoder;
+use binary::encoder::{Encoder, EncodeResult, EncodeError, Encodable};
+use binary::decoder;
+use binary::decoder::{Decoder, DecodeResult, Decodable};
+use name::Name;
 use num::FromPrimitive;
+use std::cmp;
+
+/// Largest message that fits the two-octet length prefix used over TCP.
+const MAX_MESSAGE_LENGTH: usize = 0xffff;
 
 #[derive(Debug,PartialEq)]
 pub struct Message {
@@ -22,320 +28,210 @@ impl Message {
     pub fn new(id: u16,
                operation: Operation,
                recursive: bool,
-               names: Vec<String>,
-               query_type: ResourceType) -> Message {
-        let flag = Flag{
+               names: Vec<Name>,
+               query_type: ResourceType,
+               query_class: ResourceClass) -> Message {
+        let mut builder = MessageBuilder::new(id)
+            .operation(operation)
+            .recursion_desired(recursive);
+        for name in names {
+            builder = builder.question(name, query_type, query_class);
+        }
+        builder.build()
+    }
+
+    pub fn decode(data: &[u8]) -> DecodeResult<Message> {
+        decoder::decode(data)
+    }
+}
+
+/// Payload size advertised when EDNS is turned on without giving one.
+const DEFAULT_UDP_PAYLOAD_SIZE: u16 = 4096;
+
+/// Builds a `Message` step by step, filling in the section counts.
+///
+/// A new builder describes a query with every flag cleared. Once EDNS is
+/// turned on by `edns`, `edns_option` or `dnssec_ok`, `build` appends an
+/// OPT record that carries the options and the upper bits of the response
+/// code. An extended response code turns EDNS on by itself. That OPT
+/// record replaces any the caller added with `additional`, so a message
+/// never carries two.
+pub struct MessageBuilder {
+    message: Message,
+    udp_payload_size: Option<u16>,
+    dnssec_ok: bool,
+    options: Vec<EdnsOption>,
+}
+
+impl MessageBuilder {
+    pub fn new(id: u16) -> MessageBuilder {
+        let flag = Flag {
             query_or_response: QR::Query,
-            operation: operation,
+            operation: Operation::StandardQuery,
             authorative: false,
             truncation: false,
-            recursion_desired: recursive,
+            recursion_desired: false,
             recursion_available: false,
+            z: false,
+            authentic_data: false,
+            checking_disabled: false,
             response_code: ResponseCode::NoError,
         };
-        let mut records = Vec::new();
-        let names_count = names.len() as u16;
-        for name in names {
-            let record = QuestionRecord{
-                domain_name: name,
-                query_type: query_type.clone(),
-                query_class: ResourceClass::IN,
-            };
-            records.push(record);
-        }
-        Message{
-            identity: id,
-            flag: flag,
-            question_count: names_count,
-            answer_pr_count: 0,
-            authorative_pr_count: 0,
-            additional_pr_count: 0,
-            question_record: records,
-            answer_record: Vec::new(),
-            authorative_record: Vec::new(),
-            additional_record: Vec::new(),
+        MessageBuilder {
+            message: Message {
+                identity: id,
+                flag: flag,
+                question_count: 0,
+                answer_pr_count: 0,
+                authorative_pr_count: 0,
+                additional_pr_count: 0,
+                question_record: Vec::new(),
+                answer_record: Vec::new(),
+                authorative_record: Vec::new(),
+                additional_record: Vec::new(),
+            },
+            udp_payload_size: None,
+            dnssec_ok: false,
+            options: Vec::new(),
         }
     }
 
-    fn read_u8(idx: &mut usize, data: &[u8]) -> u8 {
-        let u = data[*idx];
-        *idx = *idx + 1;
-        u
-    }
-    fn read_u16(idx: &mut usize, data: &[u8]) -> u16 {
-        let a = (data[*idx] as u16) << 8;
-        *idx = *idx + 1;
-        let b = data[*idx] as u16;
-        *idx = *idx + 1;
-        a | b
-    }
-    fn read_u32(idx: &mut usize, data: &[u8]) -> u32 {
-        let a = (data[*idx] as u32) << 24;
-        *idx = *idx + 1;
-        let b = (data[*idx] as u32) << 16;
-        *idx = *idx + 1;
-        let c = (data[*idx] as u32) << 8;
-        *idx = *idx + 1;
-        let d = data[*idx] as u32;
-        *idx = *idx + 1;
-        a | b | c | d
-    }
-    fn read_resource_type(idx: &mut usize, data: &[u8]) -> Result<ResourceType, DecodeError> {
-        let mut idx = idx;
-        let n = Message::read_u16(&mut idx, data);
-        match ResourceType::from_u16(n) {
-            Some(v) => Ok(v),
-            None    => Err(DecodeError::InvalidFormatErr("Invalid ResourceType".to_string())),
-        }
-    }
-    fn read_name(idx: &mut usize, data: &[u8]) -> Result<String, DecodeError> {
-        if data[*idx] & 0xc0 == 0xc0 {
-            let msb = ((data[*idx] & 0x3f) as u16) << 8;
-            *idx = *idx + 1;
-            let lsb = data[*idx] as u16;
-            let mut pointer: usize = (msb | lsb) as usize;
-            let res = Message::read_name(&mut pointer, data);
-            *idx = *idx + 1;
-            res
-        } else {
-            let mut idx = idx;
-            Message::read_label(&mut idx, data)
-        }
-    }
-    fn read_label(idx: &mut usize, data: &[u8]) -> Result<String, DecodeError> {
-        let mut idx = idx;
-        let mut split = Vec::new();
-        while (data[*idx] != 0)  && (data[*idx] & 0xc0 != 0xc0) {
-            let len = data[*idx];
-            *idx = *idx + 1;
-            let mut part = String::new();
-            for _ in 0..len {
-                match char::from_u32(data[*idx] as u32) {
-                    Some(c) => part.push(c),
-                    None    => return Err(DecodeError::InvalidFormatErr("Invalid Name".to_string())),
-                }
-                *idx = *idx + 1;
-            }
-            split.push(part);
-        }
-        if data[*idx] & 0xc0 == 0xc0 {
-            let part = match Message::read_name(&mut idx, data) {
-                Ok(v) => v,
-                Err(e) => return Err(e),
-            };
-            split.push(part)
-        } else {
-            *idx = *idx + 1;
-        }
-        let name = split.join(&".");
-        Ok(name)
+    pub fn query_or_response(mut self, qr: QR) -> MessageBuilder {
+        self.message.flag.query_or_response = qr;
+        self
     }
-    fn read_question_record(idx: &mut usize, data: &[u8]) -> Result<QuestionRecord, DecodeError> {
-        let mut idx = idx;
-        let name = match Message::read_name(&mut idx, data) {
-            Ok(v) => v,
-            Err(e)  => return Err(e),
-        };
-        let record_type = match Message::read_resource_type(&mut idx, data) {
-            Ok(v) => v,
-            Err(e) => return Err(e),
-        };
-        let record_class = match Message::read_u16(&mut idx, data) {
-            1 => ResourceClass::IN,
-            _  => return Err(DecodeError::InvalidFormatErr("Unknown or Not Supported Resource Class".to_string()))
-        };
-        let record = QuestionRecord{
+    pub fn operation(mut self, operation: Operation) -> MessageBuilder {
+        self.message.flag.operation = operation;
+        self
+    }
+    pub fn authorative(mut self, v: bool) -> MessageBuilder {
+        self.message.flag.authorative = v;
+        self
+    }
+    pub fn truncation(mut self, v: bool) -> MessageBuilder {
+        self.message.flag.truncation = v;
+        self
+    }
+    pub fn recursion_desired(mut self, v: bool) -> MessageBuilder {
+        self.message.flag.recursion_desired = v;
+        self
+    }
+    pub fn recursion_available(mut self, v: bool) -> MessageBuilder {
+        self.message.flag.recursion_available = v;
+        self
+    }
+    pub fn authentic_data(mut self, v: bool) -> MessageBuilder {
+        self.message.flag.authentic_data = v;
+        self
+    }
+    pub fn checking_disabled(mut self, v: bool) -> MessageBuilder {
+        self.message.flag.checking_disabled = v;
+        self
+    }
+    pub fn response_code(mut self, code: ResponseCode) -> MessageBuilder {
+        self.message.flag.response_code = code;
+        self
+    }
+
+    pub fn question(mut self, name: Name, query_type: ResourceType, query_class: ResourceClass) -> MessageBuilder {
+        self.message.question_record.push(QuestionRecord {
             domain_name: name,
-            query_type: record_type,
-            query_class: record_class,
-        };
-        Ok(record)
+            query_type: query_type,
+            query_class: query_class,
+        });
+        self
     }
-    fn read_resource_record(idx: &mut usize, data: &[u8]) -> Result<Resource, DecodeError> {
-        let mut idx = idx;
-        let name = match Message::read_name(&mut idx, data) {
-            Ok(v) => v,
-            Err(e)  => return Err(e),
-        };
-        let record_type = match Message::read_resource_type(&mut idx, data) {
-            Ok(v) => v,
-            Err(e) => return Err(e),
-        };
-        let record_class = match Message::read_u16(&mut idx, data) {
-            1 => ResourceClass::IN,
-            _  => return Err(DecodeError::InvalidFormatErr("Unknown or Not Supported Resource Class".to_string()))
-        };
-        let ttl = Message::read_u32(&mut idx, data);
-        let _ = Message::read_u16(&mut idx, data); // TODO use length
-        let rdata = match record_type {
-            ResourceType::A => {
-                let mut idx = idx;
-                RData::A(Ipv4Addr::from(Message::read_u32(&mut idx, data)))
-            },
-            ResourceType::CNAME => {
-                let mut idx = idx;
-                let name = match Message::read_name(&mut idx, data) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                };
-                RData::CNAME(name)
-            },
-            ResourceType::AAAA => {
-                let mut idx = idx;
-                RData::AAAA(Ipv6Addr::new(
-                    Message::read_u16(&mut idx, data),
-                    Message::read_u16(&mut idx, data),
-                    Message::read_u16(&mut idx, data),
-                    Message::read_u16(&mut idx, data),
-                    Message::read_u16(&mut idx, data),
-                    Message::read_u16(&mut idx, data),
-                    Message::read_u16(&mut idx, data),
-                    Message::read_u16(&mut idx, data),
-                ))
-            },
-            ResourceType::SOA => {
-                let mut idx = idx;
-                let ns = match Message::read_name(&mut idx, data) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                };
-                let mb = match Message::read_name(&mut idx, data) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                };
-                RData::SOA(SOAData::new(
-                    ns, mb,
-                    Message::read_u32(&mut idx, data),
-                    Message::read_u32(&mut idx, data),
-                    Message::read_u32(&mut idx, data),
-                    Message::read_u32(&mut idx, data),
-                    Message::read_u32(&mut idx, data)
-                ))
-            },
-            ResourceType::PTR => {
-                let mut idx = idx;
-                let name = match Message::read_name(&mut idx, data) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                };
-                RData::PTR(name)
-            },
-            ResourceType::NS => {
-                let mut idx = idx;
-                let name = match Message::read_name(&mut idx, data) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                };
-                RData::NS(name)
-            },
-            ResourceType::MX => {
-                let mut idx = idx;
-                let preference = Message::read_u16(&mut idx, data);
-                let name = match Message::read_name(&mut idx, data) {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                };
-                RData::MX(MXData::new(
-                    preference,
-                    name
-                ))
-            }
-            ty => panic!(format!("not supported type {:?}", ty)),
-        };
-        let resource = Resource {
-            name: name,
-            rtype: record_type,
-            rclass: record_class,
-            ttl: ttl,
-            rdata: rdata,
-        };
-        Ok(resource)
+    pub fn answer(mut self, record: Resource) -> MessageBuilder {
+        self.message.answer_record.push(record);
+        self
+    }
+    pub fn authority(mut self, record: Resource) -> MessageBuilder {
+        self.message.authorative_record.push(record);
+        self
+    }
+    pub fn additional(mut self, record: Resource) -> MessageBuilder {
+        self.message.additional_record.push(record);
+        self
+    }
+
+    /// Turns EDNS on, advertising `udp_payload_size`.
+    pub fn edns(mut self, udp_payload_size: u16) -> MessageBuilder {
+        self.udp_payload_size = Some(udp_payload_size);
+        self
+    }
+    /// Sets the DO bit (RFC 3225).
+    pub fn dnssec_ok(mut self, v: bool) -> MessageBuilder {
+        self.dnssec_ok = v;
+        self.enable_edns()
+    }
+    pub fn edns_option(mut self, option: EdnsOption) -> MessageBuilder {
+        self.options.push(option);
+        self.enable_edns()
+    }
+
+    fn enable_edns(mut self) -> MessageBuilder {
+        if self.udp_payload_size.is_none() {
+            self.udp_payload_size = Some(DEFAULT_UDP_PAYLOAD_SIZE);
+        }
+        self
     }
-}
 
-pub enum DecodeError {
-    InvalidFormatErr(String),
+    pub fn build(self) -> Message {
+        let code = self.message.flag.response_code.to_u16();
+        let builder = if code > 0x0f { self.enable_edns() } else { self };
+        let mut message = builder.message;
+        if let Some(size) = builder.udp_payload_size {
+            // extended RCODE, then version 0, then the DO bit (RFC 6891 6.1.3)
+            let mut ttl = ((code >> 4) as u32) << 24;
+            if builder.dnssec_ok {
+                ttl = ttl | 0x8000;
+            }
+            message.additional_record.retain(|r| r.rtype != ResourceType::OPT);
+            message.additional_record.push(Resource {
+                name: Name::root(),
+                rtype: ResourceType::OPT,
+                rclass: ResourceClass::from_u16(size).unwrap(),
+                ttl: ttl,
+                rdata: RData::OPT(OPTData::new(builder.options)),
+            });
+        }
+        message.question_count = message.question_record.len() as u16;
+        message.answer_pr_count = message.answer_record.len() as u16;
+        message.authorative_pr_count = message.authorative_record.len() as u16;
+        message.additional_pr_count = message.additional_record.len() as u16;
+        message
+    }
 }
 
-impl<'a> Message {
-    pub fn decode(data: &'a [u8]) -> Result<Message, DecodeError> {
-        let mut idx = 0;
-        let id = Message::read_u16(&mut idx, data);
-        let flag_msb = Message::read_u8(&mut idx, data);
-        let qr = if flag_msb & 0x80 == 0x80 {
-            QR::Response
-        } else {
-            QR::Query
-        };
-        let op = match flag_msb & 0x78 {
-            0 => Operation::StandardQuery,
-            1 => Operation::InverseQuery,
-            2 => Operation::ServerStatusRequest,
-            n => {
-                println!("Operation: {:?}", n);
-                return Err(DecodeError::InvalidFormatErr("Unknown Operation".to_string()));
-            },
-        };
-        let aa = flag_msb & 0x04 == 0x04;
-        let tc = flag_msb & 0x02 == 0x02;
-        let rd = flag_msb & 0x01 == 0x01;
-        let flag_lsb = Message::read_u8(&mut idx, data);
-        let ra = flag_lsb & 0x80 == 0x80;
-        let rcode = match flag_lsb & 0x0f  {
-             0 => ResponseCode::NoError,
-             1 => ResponseCode::FormatError,
-             2 => ResponseCode::ServerError,
-             3 => ResponseCode::NameError,
-             4 => ResponseCode::NotImplementedError,
-             5 => ResponseCode::RequestDenied,
-             _ => return Err(DecodeError::InvalidFormatErr("Unknown Response Code".to_string())),
-        };
-        let flag = Flag {
-            query_or_response: qr,
-            operation: op,
-            authorative: aa,
-            truncation: tc,
-            recursion_desired: rd,
-            recursion_available: ra,
-            response_code: rcode,
-        };
+impl Decodable for Message {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<Message> {
+        let id = try!(decoder.read_u16());
+        let mut flag = try!(Flag::decode(decoder));
 
-        let question_count = Message::read_u16(&mut idx, data);
-        let answer_count = Message::read_u16(&mut idx, data);
-        let authorative_count = Message::read_u16(&mut idx, data);
-        let additional_count = Message::read_u16(&mut idx, data);
+        let question_count = try!(decoder.read_u16());
+        let answer_count = try!(decoder.read_u16());
+        let authorative_count = try!(decoder.read_u16());
+        let additional_count = try!(decoder.read_u16());
 
         let mut question_records = Vec::new();
         for _ in 0..question_count {
-            let question_record = Message::read_question_record(&mut idx, data);
-            match question_record {
-                Ok(v)  => question_records.push(v),
-                Err(e) => return Err(e),
-            };
+            question_records.push(try!(QuestionRecord::decode(decoder)));
         }
         let mut answer_records = Vec::new();
         for _ in 0..answer_count {
-            let answer_record = Message::read_resource_record(&mut idx, data);
-            match answer_record {
-                Ok(v)  => answer_records.push(v),
-                Err(e) => return Err(e),
-            };
+            answer_records.push(try!(Resource::decode(decoder)));
         }
         let mut authorative_records = Vec::new();
         for _ in 0..authorative_count {
-            let authorative_record = Message::read_resource_record(&mut idx, data);
-            match authorative_record {
-                Ok(v)  => authorative_records.push(v),
-                Err(e) => return Err(e),
-            };
+            authorative_records.push(try!(Resource::decode(decoder)));
         }
         let mut additional_records = Vec::new();
         for _ in 0..additional_count {
-            let additional_record = Message::read_resource_record(&mut idx, data);
-            match additional_record {
-                Ok(v)  => additional_records.push(v),
-                Err(e) => return Err(e),
-            };
+            additional_records.push(try!(Resource::decode(decoder)));
+        }
+        // the OPT record carries the upper 8 bits of the 12-bit RCODE
+        if let Some(opt) = additional_records.iter().find(|r| r.rtype == ResourceType::OPT) {
+            flag.response_code = flag.response_code.extend(opt.ttl);
         }
         let message = Message{
             identity: id,
@@ -355,17 +251,153 @@ impl<'a> Message {
 
 impl Encodable for Message {
     fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(check_count("question", self.question_count, self.question_record.len()));
+        try!(check_count("answer", self.answer_pr_count, self.answer_record.len()));
+        try!(check_count("authority", self.authorative_pr_count, self.authorative_record.len()));
+        try!(check_count("additional", self.additional_pr_count, self.additional_record.len()));
+        try!(check_response_code(self.flag.response_code, &self.additional_record));
         try!(encoder.emit_u16(self.identity));
         try!(self.flag.encode(encoder));
         try!(encoder.emit_u16(self.question_count));
         try!(encoder.emit_u16(self.answer_pr_count));
         try!(encoder.emit_u16(self.authorative_pr_count));
         try!(encoder.emit_u16(self.additional_pr_count));
-        encoder.emit_vec(&self.question_record)
+        try!(encoder.emit_vec(&self.question_record));
+        let opt = extended_opt(&self.additional_record);
+        for section in [&self.answer_record, &self.authorative_record, &self.additional_record].iter() {
+            for record in section.iter() {
+                try!(encode_record(record, opt, self.flag.response_code, encoder));
+            }
+        }
+        if encoder.position() > MAX_MESSAGE_LENGTH {
+            return Err(EncodeError::MessageTooLarge { length: encoder.position() });
+        }
+        Ok(())
+    }
+}
+
+impl Message {
+    /// Encodes the message in at most `max_length` octets, such as 512 for
+    /// plain UDP or the payload size advertised over EDNS.
+    ///
+    /// Whole RRsets are dropped from the end of the additional section
+    /// first, then from the authority section and only then from the
+    /// answer section, and the counts are adjusted. TC is set once answer
+    /// or authority data is lost; a
+    /// shortened additional section alone does not set it (RFC 2181 9). The
+    /// OPT record is always kept. A `max_length` above 65535 is treated as
+    /// 65535, the most any transport can carry.
+    pub fn encode_with_limit(&self, max_length: usize) -> EncodeResult<Vec<u8>> {
+        let max_length = cmp::min(max_length, MAX_MESSAGE_LENGTH);
+        try!(check_count("question", self.question_count, self.question_record.len()));
+        try!(check_count("answer", self.answer_pr_count, self.answer_record.len()));
+        try!(check_count("authority", self.authorative_pr_count, self.authorative_record.len()));
+        try!(check_count("additional", self.additional_pr_count, self.additional_record.len()));
+        try!(check_response_code(self.flag.response_code, &self.additional_record));
+        let mut limited = Limited {
+            message: self,
+            flag: self.flag.clone(),
+            answer: rrsets(&self.answer_record),
+            authority: rrsets(&self.authorative_record),
+            additional: rrsets(&self.additional_record),
+        };
+        loop {
+            let encoded = try!(encoder::encode(&limited));
+            if encoded.len() <= max_length {
+                return Ok(encoded);
+            }
+            if let Some(i) = limited.additional.iter().rposition(|set| set[0].rtype != ResourceType::OPT) {
+                limited.additional.remove(i);
+            } else if limited.authority.pop().is_some() || limited.answer.pop().is_some() {
+                limited.flag.truncation = true;
+            } else {
+                return Err(EncodeError::MessageTooLarge { length: encoded.len() });
+            }
+        }
+    }
+}
+
+/// Groups records into RRsets, in the order each set first appears.
+fn rrsets(records: &[Resource]) -> Vec<Vec<&Resource>> {
+    let mut sets: Vec<Vec<&Resource>> = Vec::new();
+    for record in records {
+        let position = sets.iter().position(|set| {
+            set[0].rtype == record.rtype && set[0].rclass == record.rclass
+                && set[0].name == record.name
+        });
+        match position {
+            Some(i) => sets[i].push(record),
+            None => sets.push(vec![record]),
+        }
+    }
+    sets
+}
+
+/// A message with some of its RRsets left out.
+struct Limited<'a> {
+    message: &'a Message,
+    flag: Flag,
+    answer: Vec<Vec<&'a Resource>>,
+    authority: Vec<Vec<&'a Resource>>,
+    additional: Vec<Vec<&'a Resource>>,
+}
+
+impl<'a> Encodable for Limited<'a> {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        let sections = [&self.answer, &self.authority, &self.additional];
+        try!(encoder.emit_u16(self.message.identity));
+        try!(self.flag.encode(encoder));
+        try!(encoder.emit_u16(self.message.question_count));
+        for section in sections.iter() {
+            let count: usize = section.iter().map(|set| set.len()).sum();
+            try!(encoder.emit_u16(count as u16));
+        }
+        try!(encoder.emit_vec(&self.message.question_record));
+        let opt = extended_opt(&self.message.additional_record);
+        for section in sections.iter() {
+            for set in section.iter() {
+                for record in set {
+                    try!(encode_record(record, opt, self.flag.response_code, encoder));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The OPT record that carries the upper bits of the response code: the
+/// first one in the additional section, as `Message::decode` reads it.
+fn extended_opt(additional: &[Resource]) -> Option<&Resource> {
+    additional.iter().find(|r| r.rtype == ResourceType::OPT)
+}
+
+/// Writes a record, giving `opt` the upper 8 bits of `response_code` in
+/// its TTL (RFC 6891 6.1.3), so that the header flag is the only place the
+/// code is kept. Any other OPT record is written as it is.
+fn encode_record(record: &Resource, opt: Option<&Resource>, response_code: ResponseCode, encoder: &mut Encoder) -> EncodeResult<()> {
+    if opt.map_or(false, |opt| opt as *const Resource == record as *const Resource) {
+        let upper = (response_code.to_u16() >> 4) as u32;
+        return record.encode_with_ttl(encoder, (record.ttl & 0x00ffffff) | (upper << 24));
     }
+    record.encode(encoder)
 }
 
-#[derive(Debug, PartialEq)]
+fn check_response_code(response_code: ResponseCode, additional: &[Resource]) -> EncodeResult<()> {
+    let code = response_code.to_u16();
+    if code > 0x0f && !additional.iter().any(|r| r.rtype == ResourceType::OPT) {
+        return Err(EncodeError::ExtendedResponseCodeWithoutOpt { code: code });
+    }
+    Ok(())
+}
+
+fn check_count(section: &'static str, count: u16, records: usize) -> EncodeResult<()> {
+    if count as usize != records {
+        return Err(EncodeError::CountMismatch { section: section, count: count, records: records });
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Flag {
     pub query_or_response: QR,
     pub operation: Operation,
@@ -373,6 +405,12 @@ pub struct Flag {
     pub truncation: bool,
     pub recursion_desired: bool,
     pub recursion_available: bool,
+    /// The reserved bit, which must be zero but is kept for round trips.
+    pub z: bool,
+    /// AD, set by a validating resolver (RFC 4035 3.2.3).
+    pub authentic_data: bool,
+    /// CD, asks the resolver not to validate (RFC 4035 3.2.2).
+    pub checking_disabled: bool,
     pub response_code: ResponseCode,
 }
 
@@ -382,13 +420,33 @@ impl Encodable for Flag {
         let qr = (self.query_or_response.clone() as u8) << 7;
         msb = msb | qr;
 
-        let op = (self.operation.clone() as u8) << 3;
+        let op = self.operation.to_u8() << 3;
         msb = msb | op;
 
+        if self.authorative {
+            msb = msb | 0b00000100;
+        }
+        if self.truncation {
+            msb = msb | 0b00000010;
+        }
         if self.recursion_desired {
             msb = msb | 0b00000001;
         }
-        let lsb = 0u8;
+
+        // only the lower 4 bits fit here; the rest belongs in the OPT record
+        let mut lsb = (self.response_code.to_u16() & 0x0f) as u8;
+        if self.recursion_available {
+            lsb = lsb | 0b10000000;
+        }
+        if self.z {
+            lsb = lsb | 0b01000000;
+        }
+        if self.authentic_data {
+            lsb = lsb | 0b00100000;
+        }
+        if self.checking_disabled {
+            lsb = lsb | 0b00010000;
+        }
         match encoder.emit_u8(msb) {
             Err(s) => return Err(s),
             _ => {},
@@ -397,6 +455,31 @@ impl Encodable for Flag {
     }
 }
 
+impl Decodable for Flag {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<Flag> {
+        let msb = try!(decoder.read_u8());
+        let qr = if msb & 0x80 == 0x80 {
+            QR::Response
+        } else {
+            QR::Query
+        };
+        let op = Operation::from_u8((msb & 0x78) >> 3).unwrap();
+        let lsb = try!(decoder.read_u8());
+        Ok(Flag {
+            query_or_response: qr,
+            operation: op,
+            authorative: msb & 0x04 == 0x04,
+            truncation: msb & 0x02 == 0x02,
+            recursion_desired: msb & 0x01 == 0x01,
+            recursion_available: lsb & 0x80 == 0x80,
+            z: lsb & 0x40 == 0x40,
+            authentic_data: lsb & 0x20 == 0x20,
+            checking_disabled: lsb & 0x10 == 0x10,
+            response_code: ResponseCode::from_u16((lsb & 0x0f) as u16).unwrap(),
+        })
+    }
+}
+
 #[derive(Clone,Debug,PartialEq)]
 pub enum QR {
     Query    = 0,
@@ -405,50 +488,188 @@ pub enum QR {
 
 #[derive(Clone,Debug,PartialEq)]
 pub enum Operation {
-    StandardQuery       = 0,
-    InverseQuery        = 1,
-    ServerStatusRequest = 2,
+    StandardQuery,
+    InverseQuery,
+    ServerStatusRequest,
+    Notify,
+    Update,
+    /// DNS Stateful Operations (RFC 8490).
+    DSO,
+    /// Any other 4-bit opcode, kept so that the header still round-trips.
+    Unknown(u8),
 }
-#[derive(Clone,Debug,PartialEq)]
+
+impl FromPrimitive for Operation {
+    fn from_i64(n: i64) -> Option<Self> {
+        match n {
+            0 => Some(Operation::StandardQuery),
+            1 => Some(Operation::InverseQuery),
+            2 => Some(Operation::ServerStatusRequest),
+            4 => Some(Operation::Notify),
+            5 => Some(Operation::Update),
+            6 => Some(Operation::DSO),
+            n if n >= 0 && n <= 0x0f => Some(Operation::Unknown(n as u8)),
+            _ => None,
+        }
+    }
+    fn from_u64(n: u64) -> Option<Self> {
+        if n > 0x0f {
+            return None;
+        }
+        Operation::from_i64(n as i64)
+    }
+}
+
+impl Operation {
+    pub fn to_u8(&self) -> u8 {
+        match *self {
+            Operation::StandardQuery       => 0,
+            Operation::InverseQuery        => 1,
+            Operation::ServerStatusRequest => 2,
+            Operation::Notify              => 4,
+            Operation::Update              => 5,
+            Operation::DSO                 => 6,
+            Operation::Unknown(n)          => n & 0x0f,
+        }
+    }
+}
+#[derive(Copy,Clone,Debug,PartialEq)]
 pub enum ResponseCode {
-    NoError             = 0,
-    FormatError         = 1,
-    ServerError         = 2,
-    NameError           = 3,
-    NotImplementedError = 4,
-    RequestDenied       = 5,
+    NoError,
+    FormatError,
+    ServerError,
+    NameError,
+    NotImplementedError,
+    RequestDenied,
+    YXDomain,
+    YXRRSet,
+    NXRRSet,
+    NotAuth,
+    NotZone,
+    DSOTypeNI,
+    /// BADVERS in an OPT record, BADSIG in a TSIG record (both 16).
+    BadVersion,
+    BadKey,
+    BadTime,
+    BadMode,
+    BadName,
+    BadAlgorithm,
+    BadTruncation,
+    BadCookie,
+    Unknown(u16),
+}
+
+impl FromPrimitive for ResponseCode {
+    fn from_i64(n: i64) -> Option<Self> {
+        match n {
+            0  => Some(ResponseCode::NoError),
+            1  => Some(ResponseCode::FormatError),
+            2  => Some(ResponseCode::ServerError),
+            3  => Some(ResponseCode::NameError),
+            4  => Some(ResponseCode::NotImplementedError),
+            5  => Some(ResponseCode::RequestDenied),
+            6  => Some(ResponseCode::YXDomain),
+            7  => Some(ResponseCode::YXRRSet),
+            8  => Some(ResponseCode::NXRRSet),
+            9  => Some(ResponseCode::NotAuth),
+            10 => Some(ResponseCode::NotZone),
+            11 => Some(ResponseCode::DSOTypeNI),
+            16 => Some(ResponseCode::BadVersion),
+            17 => Some(ResponseCode::BadKey),
+            18 => Some(ResponseCode::BadTime),
+            19 => Some(ResponseCode::BadMode),
+            20 => Some(ResponseCode::BadName),
+            21 => Some(ResponseCode::BadAlgorithm),
+            22 => Some(ResponseCode::BadTruncation),
+            23 => Some(ResponseCode::BadCookie),
+            n if n >= 0 && n <= 0xfff => Some(ResponseCode::Unknown(n as u16)),
+            _  => None,
+        }
+    }
+    fn from_u64(n: u64) -> Option<Self> {
+        if n > 0xfff {
+            return None;
+        }
+        ResponseCode::from_i64(n as i64)
+    }
+}
+
+impl ResponseCode {
+    /// Combines the 4 bits from the header with the upper 8 bits carried in
+    /// the TTL of an OPT record into the 12-bit extended RCODE.
+    pub fn extend(&self, opt_ttl: u32) -> ResponseCode {
+        let upper = (opt_ttl >> 24) as u16;
+        let code = (upper << 4) | (self.to_u16() & 0x0f);
+        ResponseCode::from_u16(code).unwrap()
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        match *self {
+            ResponseCode::NoError             => 0,
+            ResponseCode::FormatError         => 1,
+            ResponseCode::ServerError         => 2,
+            ResponseCode::NameError           => 3,
+            ResponseCode::NotImplementedError => 4,
+            ResponseCode::RequestDenied       => 5,
+            ResponseCode::YXDomain            => 6,
+            ResponseCode::YXRRSet             => 7,
+            ResponseCode::NXRRSet             => 8,
+            ResponseCode::NotAuth             => 9,
+            ResponseCode::NotZone             => 10,
+            ResponseCode::DSOTypeNI           => 11,
+            ResponseCode::BadVersion          => 16,
+            ResponseCode::BadKey              => 17,
+            ResponseCode::BadTime             => 18,
+            ResponseCode::BadMode             => 19,
+            ResponseCode::BadName             => 20,
+            ResponseCode::BadAlgorithm        => 21,
+            ResponseCode::BadTruncation       => 22,
+            ResponseCode::BadCookie           => 23,
+            ResponseCode::Unknown(n)          => n,
+        }
+    }
 }
 
 #[derive(Debug,PartialEq)]
 pub struct QuestionRecord {
-    pub domain_name: String,
+    pub domain_name: Name,
     pub query_type: ResourceType,
     pub query_class: ResourceClass,
 }
 
 impl Encodable for QuestionRecord {
     fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
-        let name = self.domain_name.clone();
-        let sp = name.split(".");
-        for s in sp {
-            let length = s.len();
-            try!(encoder.emit_u8(length as u8));
-            try!(encoder.emit_str(s));
-        }
-        try!(encoder.emit_u8(0));
+        try!(encoder.emit_name(&self.domain_name));
+        try!(encoder.emit_u16(self.query_type.to_u16()));
+        encoder.emit_u16(self.query_class.to_u16())
+    }
+}
 
-        try!(encoder.emit_u16(self.query_type.clone() as u16));
-        encoder.emit_u16(self.query_class.clone() as u16)
+impl Decodable for QuestionRecord {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<QuestionRecord> {
+        let name = try!(decoder.read_name());
+        let record_type = try!(ResourceType::decode(decoder));
+        let record_class = try!(ResourceClass::decode(decoder));
+        Ok(QuestionRecord{
+            domain_name: name,
+            query_type: record_type,
+            query_class: record_class,
+        })
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Message, Flag, QR, Operation, ResponseCode, QuestionRecord, DecodeError};
+    use super::{Message, MessageBuilder, Flag, QR, Operation, ResponseCode, QuestionRecord};
     use binary::encoder;
-    use binary::encoder::{Encoder, Encodable};
-    use resource::{Resource, ResourceType, ResourceClass, RData};
-    use std::net::Ipv4Addr;
+    use binary::decoder::DecodeError;
+    use binary::encoder::EncodeError;
+    use resource::{Resource, ResourceType, ResourceClass, RData, SOAData, MXData, TXTData, SRVData, WKSData, OPTData, EdnsOption,
+                   HINFOData, MINFOData, RPData, AFSDBData, X25Data, ISDNData, RTData, NAPTRData};
+    use name::Name;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::str::FromStr;
+
 
     #[test]
     fn test_query_encode() {
@@ -456,8 +677,9 @@ mod test {
             0,
             Operation::StandardQuery,
             true,
-            vec![String::from("google.com")],
-            ResourceType::A
+            vec![Name::from_str("google.com").unwrap()],
+            ResourceType::A,
+            ResourceClass::IN,
         );
         let encoded = encoder::encode(&query);
         let expected = vec![
@@ -495,7 +717,7 @@ mod test {
         ];
         let decoded = Message::decode(&mut encoded);
         let question_record = QuestionRecord {
-            domain_name: String::from("google.com"),
+            domain_name: Name::from_str("google.com").unwrap(),
             query_type: ResourceType::A,
             query_class: ResourceClass::IN,
         };
@@ -508,6 +730,9 @@ mod test {
                 truncation: false,
                 recursion_desired: true,
                 recursion_available: false,
+                z: false,
+                authentic_data: false,
+                checking_disabled: false,
                 response_code: ResponseCode::NoError,
             },
             question_count: 1,
@@ -521,8 +746,8 @@ mod test {
         };
         match decoded {
             Ok(v) => assert_eq!(v, expected),
-            Err(DecodeError::InvalidFormatErr(s)) => {
-                println!("Error {}", s);
+            Err(e) => {
+                println!("Error {}", e);
                 assert!(false)
             },
         }
@@ -552,12 +777,12 @@ mod test {
         ];
         let decoded = Message::decode(&mut encoded);
         let question_record = QuestionRecord {
-            domain_name: "google.com".to_string(),
+            domain_name: Name::from_str("google.com").unwrap(),
             query_type: ResourceType::A,
             query_class: ResourceClass::IN,
         };
         let resource_record = Resource {
-            name: "google.com".to_string(),
+            name: Name::from_str("google.com").unwrap(),
             rtype: ResourceType::A,
             rclass: ResourceClass::IN,
             ttl: 99,
@@ -572,6 +797,9 @@ mod test {
                 truncation: false,
                 recursion_desired: true,
                 recursion_available: false,
+                z: false,
+                authentic_data: false,
+                checking_disabled: false,
                 response_code: ResponseCode::NoError,
             },
             question_count: 1,
@@ -585,10 +813,629 @@ mod test {
         };
         match decoded {
             Ok(v) => assert_eq!(v, expected),
-            Err(DecodeError::InvalidFormatErr(s)) => {
-                println!("Error {}", s);
+            Err(e) => {
+                println!("Error {}", e);
                 assert!(false)
             },
         }
     }
+
+    #[test]
+    fn test_encode_compressed_response() {
+        let encoded = [
+            0u8, 0u8, // ident 0
+            1u8, 0u8, // flag recursion_desired true
+            0u8, 1u8, // question num 1
+            0u8, 1u8, // answer num 1
+            0u8, 0u8, // authorative num 0
+            0u8, 0u8, // additional num 0
+            // question google.com IN A
+            6u8, 103u8, 111u8, 111u8, 103u8, 108u8, 101u8,
+            3u8, 99u8, 111u8, 109u8,
+            0u8, // name end
+            0u8, 1u8, // type
+            0u8, 1u8, // class
+            0xc0, 0x0c, // pointer to google.com
+            0x00, 0x05, // type CNAME
+            0x00, 0x01, // class IN
+            0x00, 0x00, 0x00, 0x63, // ttl
+            0x00, 0x06, // rdata length
+            3u8, 119u8, 119u8, 119u8, // www
+            0xc0, 0x0c, // pointer to google.com
+        ];
+        let decoded = match Message::decode(&encoded) {
+            Ok(v) => v,
+            Err(_) => panic!("failed to decode"),
+        };
+        assert_eq!(encoder::encode(&decoded).unwrap(), encoded.to_vec());
+    }
+
+    #[test]
+    fn test_encode_resource_record() {
+        let encoded = [
+            0u8, 0u8, // ident 0
+            1u8, 0u8, // flag recursion_desired true
+            0u8, 0u8, // question num 0
+            0u8, 1u8, // answer num 1
+            0u8, 0u8, // authorative num 0
+            0u8, 0u8, // additional num 0
+            6u8, 103u8, 111u8, 111u8, 103u8, 108u8, 101u8,
+            3u8, 99u8, 111u8, 109u8,
+            0u8, // name end
+            0x00, 0x0f, // type MX
+            0x00, 0x01, // class IN
+            0x00, 0x00, 0x00, 0x63, // ttl
+            0x00, 0x09, // rdata length
+            0x00, 0x0a, // preference
+            3u8, 109u8, 120u8, 49u8, 1u8, 120u8, // mx1.x
+            0u8,
+        ];
+        let decoded = match Message::decode(&encoded) {
+            Ok(v) => v,
+            Err(_) => panic!("failed to decode"),
+        };
+        assert_eq!(encoder::encode(&decoded).unwrap(), encoded.to_vec());
+    }
+
+    #[test]
+    fn test_round_trip_all_rdata() {
+        let resource = |rtype: ResourceType, rdata: RData| Resource {
+            name: Name::from_str("example.com").unwrap(),
+            rtype: rtype,
+            rclass: ResourceClass::IN,
+            ttl: 3600,
+            rdata: rdata,
+        };
+        let mut message = Message::new(
+            0x1234,
+            Operation::StandardQuery,
+            true,
+            vec![Name::from_str("example.com").unwrap()],
+            ResourceType::A,
+            ResourceClass::IN,
+        );
+        message.answer_record = vec![
+            resource(ResourceType::A, RData::A(Ipv4Addr::new(192, 0, 2, 1))),
+            resource(ResourceType::AAAA, RData::AAAA(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))),
+            resource(ResourceType::CNAME, RData::CNAME(Name::from_str("www.example.com").unwrap())),
+            resource(ResourceType::MX, RData::MX(MXData::new(10, Name::from_str("mail.example.com").unwrap()))),
+            resource(ResourceType::TXT, RData::TXT(TXTData::new(vec![b"v=spf1 -all".to_vec(), vec![0, 255]]))),
+            resource(ResourceType::SRV, RData::SRV(SRVData::new(0, 5, 5060, Name::from_str("sip.example.com").unwrap()))),
+            resource(ResourceType::HINFO, RData::HINFO(HINFOData::new(b"VAX-11/780".to_vec(), b"UNIX".to_vec()))),
+            resource(ResourceType::MINFO, RData::MINFO(MINFOData::new(Name::from_str("list-request.example.com").unwrap(), Name::from_str("owner.example.com").unwrap()))),
+            resource(ResourceType::RP, RData::RP(RPData::new(Name::from_str("louie.trantor.umd.edu").unwrap(), Name::from_str("lam1.people.umd.edu").unwrap()))),
+            resource(ResourceType::AFSDB, RData::AFSDB(AFSDBData::new(1, Name::from_str("bigbird.toaster.com").unwrap()))),
+            resource(ResourceType::X25, RData::X25(X25Data::new(b"311061700956".to_vec()))),
+            resource(ResourceType::ISDN, RData::ISDN(ISDNData::new(b"150862028003217".to_vec(), Some(b"004".to_vec())))),
+            resource(ResourceType::ISDN, RData::ISDN(ISDNData::new(b"150862028003217".to_vec(), None))),
+            resource(ResourceType::RT, RData::RT(RTData::new(2, Name::from_str("relay.prime.com").unwrap()))),
+            resource(ResourceType::NAPTR, RData::NAPTR(NAPTRData::new(
+                10, 100, b"S".to_vec(), b"SIP+D2U".to_vec(), vec![], Name::from_str("_sip._udp.example.com").unwrap(),
+            ))),
+        ];
+        message.authorative_record = vec![
+            resource(ResourceType::NS, RData::NS(Name::from_str("ns1.example.com").unwrap())),
+            resource(ResourceType::SOA, RData::SOA(SOAData::new(
+                Name::from_str("ns1.example.com").unwrap(), Name::from_str("hostmaster.example.com").unwrap(),
+                2015080101, 7200, 900, 1209600, 300,
+            ))),
+        ];
+        message.additional_record = vec![
+            resource(ResourceType::PTR, RData::PTR(Name::from_str("host.example.com").unwrap())),
+            resource(ResourceType::WKS, RData::WKS(WKSData::new(Ipv4Addr::new(192, 0, 2, 1), 6, vec![1, 25]))),
+        ];
+        message.answer_pr_count = 15;
+        message.authorative_pr_count = 2;
+        message.additional_pr_count = 2;
+
+        let encoded = encoder::encode(&message).unwrap();
+        match Message::decode(&encoded) {
+            Ok(v) => assert_eq!(v, message),
+            Err(_) => panic!("failed to decode"),
+        }
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        let encoded = [
+            0u8, 0u8, // ident 0
+            1u8, 0u8, // flag recursion_desired true
+            0u8, 1u8, // question num 1
+            0u8, 1u8, // answer num 1
+            0u8, 0u8, // authorative num 0
+            0u8, 0u8, // additional num 0
+            6u8, 103u8, 111u8, 111u8, 103u8, 108u8, 101u8,
+            3u8, 99u8, 111u8, 109u8,
+            0u8, // name end
+            0u8, 1u8, // type
+            0u8, 1u8, // class
+            0xc0, 0x0c,
+            0x00, 0x01,
+            0x00, 0x01,
+            0x00, 0x00, 0x00, 0x63, // ttl
+            0x00, 0x04, // rdata
+            0xad, 0xc2, 0x7e, 0xc1, // ip
+        ];
+        assert_eq!(Message::decode(&encoded[..1]), Err(DecodeError::Truncated { offset: 0, needed: 2 }));
+        assert_eq!(Message::decode(&encoded[..15]), Err(DecodeError::Truncated { offset: 13, needed: 6 }));
+        assert_eq!(Message::decode(&encoded[..43]), Err(DecodeError::Truncated { offset: 40, needed: 4 }));
+        for len in 0..encoded.len() {
+            match Message::decode(&encoded[..len]) {
+                Err(DecodeError::Truncated { .. }) => {},
+                other => panic!("unexpected result for {} bytes: {:?}", len, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_bad_label_and_pointer() {
+        let bad_label = [
+            0u8, 0u8, 1u8, 0u8, 0u8, 1u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8,
+            0x41, 0x61, 0u8, 0u8, 1u8, 0u8, 1u8,
+        ];
+        assert_eq!(Message::decode(&bad_label), Err(DecodeError::BadLabel { offset: 12 }));
+
+        let bad_pointer = [
+            0u8, 0u8, 1u8, 0u8, 0u8, 1u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8,
+            0xc0, 0xff, 0u8, 1u8, 0u8, 1u8,
+        ];
+        assert_eq!(Message::decode(&bad_pointer), Err(DecodeError::BadPointer { offset: 12, target: 0xff }));
+    }
+
+    #[test]
+    fn test_decode_unknown_class() {
+        let encoded = [
+            0u8, 0u8, 1u8, 0u8, 0u8, 1u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8,
+            0u8, // root
+            0u8, 1u8, // type A
+            0u8, 9u8, // class 9
+        ];
+        match Message::decode(&encoded) {
+            Ok(v) => assert_eq!(v.question_record[0].query_class, ResourceClass::Unknown(9)),
+            Err(e) => panic!("failed to decode: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_chaos_query_round_trip() {
+        let query = Message::new(
+            0,
+            Operation::StandardQuery,
+            false,
+            vec![Name::from_str("version.bind").unwrap()],
+            ResourceType::TXT,
+            ResourceClass::CH,
+        );
+        let encoded = encoder::encode(&query).unwrap();
+        assert_eq!(&encoded[encoded.len() - 4..], &[0u8, 16u8, 0u8, 3u8]);
+        match Message::decode(&encoded) {
+            Ok(v) => assert_eq!(v, query),
+            Err(e) => panic!("failed to decode: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_decode_pointer_loop() {
+        // the question name is a pointer to itself
+        let self_pointer = [
+            0u8, 0u8, 1u8, 0u8, 0u8, 1u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8,
+            0xc0, 0x0c, 0u8, 1u8, 0u8, 1u8,
+        ];
+        assert_eq!(Message::decode(&self_pointer), Err(DecodeError::PointerLoop { offset: 12, target: 12 }));
+
+        // "a" followed by a pointer back to the start of the same name
+        let inner_loop = [
+            0u8, 0u8, 1u8, 0u8, 0u8, 1u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8,
+            1u8, 97u8, 0xc0, 0x0c, 0u8, 1u8, 0u8, 1u8,
+        ];
+        assert_eq!(Message::decode(&inner_loop), Err(DecodeError::PointerLoop { offset: 14, target: 12 }));
+
+        // the answer points back to the question, which points forward to the answer
+        let mutual = [
+            0u8, 0u8, 1u8, 0u8, 0u8, 1u8, 0u8, 1u8, 0u8, 0u8, 0u8, 0u8,
+            1u8, 97u8, 0xc0, 0x14, 0u8, 1u8, 0u8, 1u8,
+            0xc0, 0x0c, 0u8, 1u8, 0u8, 1u8, 0u8, 0u8, 0u8, 0u8, 0u8, 4u8, 1u8, 2u8, 3u8, 4u8,
+        ];
+        assert_eq!(Message::decode(&mutual), Err(DecodeError::BadPointer { offset: 14, target: 20 }));
+    }
+
+    #[test]
+    fn test_decode_name_too_long() {
+        let mut encoded = vec![0u8, 0u8, 1u8, 0u8, 0u8, 1u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8];
+        for _ in 0..4 {
+            encoded.push(63);
+            encoded.extend(vec![97u8; 63]);
+        }
+        encoded.extend(vec![0u8, 0u8, 1u8, 0u8, 1u8]);
+        assert_eq!(Message::decode(&encoded), Err(DecodeError::NameTooLong { offset: 12 }));
+
+        // 3 labels of 63 octets plus one of 61 is exactly 255 octets
+        encoded[12 + 3 * 64] = 61;
+        encoded.drain(12 + 3 * 64 + 62..12 + 3 * 64 + 64);
+        assert!(Message::decode(&encoded).is_ok());
+    }
+
+    #[test]
+    fn test_decode_mutated_never_panics() {
+        let encoded = [
+            0u8, 0u8, 1u8, 0u8, 0u8, 1u8, 0u8, 1u8, 0u8, 0u8, 0u8, 0u8,
+            6u8, 103u8, 111u8, 111u8, 103u8, 108u8, 101u8,
+            3u8, 99u8, 111u8, 109u8, 0u8,
+            0u8, 1u8, 0u8, 1u8,
+            3u8, 119u8, 119u8, 119u8, 0xc0, 0x0c,
+            0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0x63, 0x00, 0x02,
+            0xc0, 0x1c,
+        ];
+        assert!(Message::decode(&encoded).is_ok());
+        for i in 0..encoded.len() {
+            for &b in [0x00u8, 0x01, 0x3f, 0x40, 0x80, 0xc0, 0xff].iter() {
+                let mut mutated = encoded.to_vec();
+                mutated[i] = b;
+                let _ = Message::decode(&mutated);
+            }
+            if i + 1 < encoded.len() {
+                for target in 0..encoded.len() + 2 {
+                    let mut mutated = encoded.to_vec();
+                    mutated[i] = 0xc0;
+                    mutated[i + 1] = target as u8;
+                    let _ = Message::decode(&mutated);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_unknown_rdata() {
+        let encoded = [
+            0u8, 0u8, 0x81, 0x80, 0u8, 0u8, 0u8, 1u8, 0u8, 0u8, 0u8, 1u8,
+            // _sip._udp. IN TYPE65280 \# 9 000a003c13c4016100
+            4u8, 95u8, 115u8, 105u8, 112u8, 4u8, 95u8, 117u8, 100u8, 112u8, 0u8,
+            0xff, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x09,
+            0x00, 0x0a, 0x00, 0x3c, 0x13, 0xc4, 1u8, 97u8, 0u8,
+            // a. IN A 192.0.2.1
+            0xc0, 0x27, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x04,
+            192u8, 0u8, 2u8, 1u8,
+        ];
+        let decoded = match Message::decode(&encoded) {
+            Ok(v) => v,
+            Err(e) => panic!("failed to decode: {}", e),
+        };
+        let expected = RData::Unknown {
+            rtype: 65280,
+            data: vec![0x00, 0x0a, 0x00, 0x3c, 0x13, 0xc4, 1u8, 97u8, 0u8],
+        };
+        assert_eq!(decoded.answer_record[0].rdata, expected);
+        assert_eq!(format!("{}", expected), "\\# 9 000a003c13c4016100");
+        assert_eq!(decoded.additional_record[0].rdata, RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+
+        // the same RDATA as SRV
+        let mut encoded = encoded.to_vec();
+        encoded[23] = 0x00;
+        encoded[24] = 0x21;
+        let decoded = Message::decode(&encoded).unwrap();
+        let expected = RData::SRV(SRVData::new(10, 60, 5060, Name::from_str("a.").unwrap()));
+        assert_eq!(decoded.answer_record[0].rdata, expected);
+        assert_eq!(format!("{}", expected), "10 60 5060 a.");
+    }
+
+    #[test]
+    fn test_decode_bad_rdlength() {
+        let mut encoded = vec![
+            0u8, 0u8, 0x81, 0x80, 0u8, 0u8, 0u8, 1u8, 0u8, 0u8, 0u8, 0u8,
+            0u8, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x05,
+            192u8, 0u8, 2u8, 1u8, 0u8,
+        ];
+        assert_eq!(Message::decode(&encoded), Err(DecodeError::BadRdLength { offset: 23, length: 5 }));
+        encoded[22] = 3;
+        assert_eq!(Message::decode(&encoded[..26]), Err(DecodeError::Truncated { offset: 23, needed: 4 }));
+    }
+
+    #[test]
+    fn test_flag_round_trip() {
+        let header = |msb: u8, lsb: u8| [0x12u8, 0x34u8, msb, lsb, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8];
+        let cases = [
+            (0x00u8, 0x00u8),
+            (0x85, 0x83), // response, AA, RD, RA, NXDOMAIN
+            (0x22, 0x00), // NOTIFY, TC
+            (0x28, 0x05), // UPDATE, REFUSED
+            (0x81, 0xa0), // response, RD, RA, AD
+            (0x01, 0x10), // RD, CD
+            (0x10, 0x40), // STATUS, Z
+        ];
+        for &(msb, lsb) in cases.iter() {
+            let encoded = header(msb, lsb);
+            let decoded = match Message::decode(&encoded) {
+                Ok(v) => v,
+                Err(e) => panic!("failed to decode {:02x}{:02x}: {}", msb, lsb, e),
+            };
+            assert_eq!(encoder::encode(&decoded).unwrap(), encoded.to_vec());
+        }
+
+        let decoded = Message::decode(&header(0xad, 0xb3)).unwrap();
+        assert_eq!(decoded.flag, Flag {
+            query_or_response: QR::Response,
+            operation: Operation::Update,
+            authorative: true,
+            truncation: false,
+            recursion_desired: true,
+            recursion_available: true,
+            z: false,
+            authentic_data: true,
+            checking_disabled: true,
+            response_code: ResponseCode::NameError,
+        });
+        assert_eq!(Message::decode(&header(0x18, 0x00)).unwrap().flag.operation, Operation::Unknown(3));
+        assert_eq!(Message::decode(&header(0x30, 0x00)).unwrap().flag.operation, Operation::DSO);
+        for &msb in [0x18u8, 0x30, 0x78].iter() {
+            let encoded = header(msb, 0x00);
+            assert_eq!(encoder::encode(&Message::decode(&encoded).unwrap()).unwrap(), encoded.to_vec());
+        }
+    }
+
+    #[test]
+    fn test_decode_response_codes() {
+        let header = |lsb: u8| [0u8, 0u8, 0x80, lsb, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8];
+        assert_eq!(Message::decode(&header(6)).unwrap().flag.response_code, ResponseCode::YXDomain);
+        assert_eq!(Message::decode(&header(9)).unwrap().flag.response_code, ResponseCode::NotAuth);
+        assert_eq!(Message::decode(&header(12)).unwrap().flag.response_code, ResponseCode::Unknown(12));
+    }
+
+    #[test]
+    fn test_decode_extended_response_code() {
+        let encoded = [
+            0u8, 0u8, 0x80, 0x00, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 1u8,
+            0u8, // root
+            0x00, 0x29, // type OPT
+            0x10, 0x00, // udp payload size 4096
+            0x01, 0x00, 0x80, 0x00, // extended rcode 1, version 0, DO
+            0x00, 0x0c, // rdata length
+            0x00, 0x0a, 0x00, 0x08, 1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8, // cookie
+        ];
+        let decoded = match Message::decode(&encoded) {
+            Ok(v) => v,
+            Err(e) => panic!("failed to decode: {}", e),
+        };
+        assert_eq!(decoded.flag.response_code, ResponseCode::BadVersion);
+        assert_eq!(decoded.additional_record[0].rclass, ResourceClass::Unknown(4096));
+        assert_eq!(decoded.additional_record[0].rdata, RData::OPT(OPTData::new(vec![
+            EdnsOption::new(10, vec![1, 2, 3, 4, 5, 6, 7, 8]),
+        ])));
+        assert_eq!(encoder::encode(&decoded).unwrap(), encoded.to_vec());
+
+        // BADCOOKIE is 23: 1 in the OPT record and 7 in the header
+        let mut encoded = encoded.to_vec();
+        encoded[3] = 0x07;
+        assert_eq!(Message::decode(&encoded).unwrap().flag.response_code, ResponseCode::BadCookie);
+    }
+
+    #[test]
+    fn test_encode_extended_response_code() {
+        let encoded = [
+            0u8, 0u8, 0x80, 0x00, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 1u8,
+            0u8, 0x00, 0x29, 0x10, 0x00,
+            0x01, 0x00, 0x80, 0x00, // BADVERS, DO
+            0x00, 0x00,
+        ];
+        // the header flag wins over a stale OPT TTL
+        let mut message = Message::decode(&encoded).unwrap();
+        message.flag.response_code = ResponseCode::NoError;
+        let reencoded = encoder::encode(&message).unwrap();
+        assert_eq!(&reencoded[17..21], &[0x00, 0x00, 0x80, 0x00]);
+        assert_eq!(Message::decode(&reencoded).unwrap().flag.response_code, ResponseCode::NoError);
+        message.flag.response_code = ResponseCode::BadCookie;
+        let reencoded = message.encode_with_limit(512).unwrap();
+        assert_eq!(reencoded[3], 0x07);
+        assert_eq!(&reencoded[17..21], &[0x01, 0x00, 0x80, 0x00]);
+        assert_eq!(Message::decode(&reencoded).unwrap().flag.response_code, ResponseCode::BadCookie);
+
+        // without an OPT record the upper bits have nowhere to go
+        message.additional_record.clear();
+        message.additional_pr_count = 0;
+        assert_eq!(encoder::encode(&message), Err(EncodeError::ExtendedResponseCodeWithoutOpt { code: 23 }));
+        assert_eq!(message.encode_with_limit(512), Err(EncodeError::ExtendedResponseCodeWithoutOpt { code: 23 }));
+        message.flag.response_code = ResponseCode::NotZone;
+        assert!(encoder::encode(&message).is_ok());
+
+        // only the first OPT of the additional section is rewritten
+        let opt = |ttl| Resource {
+            name: Name::root(),
+            rtype: ResourceType::OPT,
+            rclass: ResourceClass::Unknown(4096),
+            ttl: ttl,
+            rdata: RData::OPT(OPTData::new(Vec::new())),
+        };
+        message.flag.response_code = ResponseCode::BadCookie;
+        message.answer_record = vec![opt(0x02000000)];
+        message.answer_pr_count = 1;
+        message.additional_record = vec![opt(0), opt(0x03000000)];
+        message.additional_pr_count = 2;
+        let reencoded = encoder::encode(&message).unwrap();
+        let decoded = Message::decode(&reencoded).unwrap();
+        assert_eq!(decoded.answer_record[0].ttl, 0x02000000);
+        assert_eq!(decoded.additional_record[0].ttl, 0x01000000);
+        assert_eq!(decoded.additional_record[1].ttl, 0x03000000);
+        assert_eq!(encoder::encode(&decoded), Ok(reencoded));
+        let limited = Message::decode(&message.encode_with_limit(512).unwrap()).unwrap();
+        assert_eq!(limited, decoded);
+    }
+
+    #[test]
+    fn test_binary_labels_round_trip() {
+        let encoded = [
+            0u8, 0u8, 1u8, 0u8, 0u8, 1u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8,
+            5u8, 97u8, 46u8, 98u8, 32u8, 0xff, // "a.b \255"
+            3u8, 99u8, 111u8, 109u8, 0u8,
+            0u8, 1u8, 0u8, 1u8,
+        ];
+        let decoded = match Message::decode(&encoded) {
+            Ok(v) => v,
+            Err(e) => panic!("failed to decode: {}", e),
+        };
+        assert_eq!(decoded.question_record[0].domain_name.to_string(), "a\\.b\\032\\255.com.");
+        assert_eq!(encoder::encode(&decoded).unwrap(), encoded.to_vec());
+    }
+
+    #[test]
+    fn test_encode_invalid_message() {
+        let mut query = Message::new(0, Operation::StandardQuery, true, vec![Name::from_str("example.com").unwrap()], ResourceType::A, ResourceClass::IN);
+        query.answer_pr_count = 1;
+        assert_eq!(encoder::encode(&query), Err(EncodeError::CountMismatch { section: "answer", count: 1, records: 0 }));
+
+        let mut response = Message::new(0, Operation::StandardQuery, true, vec![], ResourceType::A, ResourceClass::IN);
+        for _ in 0..300 {
+            response.answer_record.push(Resource {
+                name: Name::from_str("example.com").unwrap(),
+                rtype: ResourceType::Unknown(65280),
+                rclass: ResourceClass::IN,
+                ttl: 0,
+                rdata: RData::Unknown { rtype: 65280, data: vec![0u8; 255] },
+            });
+        }
+        response.answer_pr_count = 300;
+        match encoder::encode(&response) {
+            Err(EncodeError::MessageTooLarge { .. }) => {},
+            other => panic!("unexpected result {:?}", other),
+        }
+        // a larger limit does not let it through either
+        let decoded = Message::decode(&response.encode_with_limit(100000).unwrap()).unwrap();
+        assert!(decoded.flag.truncation);
+        assert_eq!(decoded.answer_record.len(), 0);
+    }
+
+    #[test]
+    fn test_encode_with_limit() {
+        let record = |owner: &str, rtype: ResourceType, rdata: RData| Resource {
+            name: Name::from_str(owner).unwrap(),
+            rtype: rtype,
+            rclass: ResourceClass::IN,
+            ttl: 300,
+            rdata: rdata,
+        };
+        let mut builder = MessageBuilder::new(0x1234)
+            .query_or_response(QR::Response)
+            .recursion_desired(true)
+            .question(Name::from_str("example.com").unwrap(), ResourceType::MX, ResourceClass::IN)
+            .edns(512);
+        for i in 0..20 {
+            builder = builder
+                .answer(record("example.com", ResourceType::MX,
+                    RData::MX(MXData::new(i, Name::from_str(&format!("mx{}.example.com", i)).unwrap()))))
+                .additional(record(&format!("mx{}.example.com", i), ResourceType::A,
+                    RData::A(Ipv4Addr::new(192, 0, 2, i as u8))));
+        }
+        let response = builder.build();
+
+        let full = encoder::encode(&response).unwrap();
+        assert_eq!(response.encode_with_limit(full.len()).unwrap(), full);
+
+        // the A records go first, one RRset at a time, and TC stays clear
+        let encoded = response.encode_with_limit(512).unwrap();
+        assert!(encoded.len() <= 512);
+        let decoded = Message::decode(&encoded).unwrap();
+        assert!(!decoded.flag.truncation);
+        assert_eq!(decoded.answer_record.len(), 20);
+        assert_eq!(decoded.additional_pr_count as usize, decoded.additional_record.len());
+        assert!(decoded.additional_record.len() < 21);
+        assert_eq!(decoded.additional_record.last().unwrap().rtype, ResourceType::OPT);
+
+        // the MX RRset cannot be split, so it goes as a whole
+        let encoded = response.encode_with_limit(200).unwrap();
+        let decoded = Message::decode(&encoded).unwrap();
+        assert!(decoded.flag.truncation);
+        assert_eq!(decoded.answer_pr_count, 0);
+        assert_eq!(decoded.answer_record.len(), 0);
+        assert_eq!(decoded.additional_record.len(), 1);
+        assert_eq!(decoded.question_record, response.question_record);
+
+        match response.encode_with_limit(20) {
+            Err(EncodeError::MessageTooLarge { .. }) => {},
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_encode_with_limit_keeps_answers() {
+        let response = MessageBuilder::new(1)
+            .query_or_response(QR::Response)
+            .question(Name::from_str("example.com").unwrap(), ResourceType::A, ResourceClass::IN)
+            .answer(Resource {
+                name: Name::from_str("example.com").unwrap(),
+                rtype: ResourceType::A,
+                rclass: ResourceClass::IN,
+                ttl: 300,
+                rdata: RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            })
+            .authority(Resource {
+                name: Name::from_str("example.com").unwrap(),
+                rtype: ResourceType::NS,
+                rclass: ResourceClass::IN,
+                ttl: 300,
+                rdata: RData::NS(Name::from_str("ns1.example.com").unwrap()),
+            })
+            .build();
+        let full = encoder::encode(&response).unwrap();
+        let decoded = Message::decode(&response.encode_with_limit(full.len() - 1).unwrap()).unwrap();
+        assert!(decoded.flag.truncation);
+        assert_eq!(decoded.answer_record.len(), 1);
+        assert_eq!(decoded.authorative_record.len(), 0);
+    }
+
+    #[test]
+    fn test_message_builder() {
+        let a = Resource {
+            name: Name::from_str("www.example.com").unwrap(),
+            rtype: ResourceType::A,
+            rclass: ResourceClass::IN,
+            ttl: 60,
+            rdata: RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+        };
+        let message = MessageBuilder::new(7)
+            .query_or_response(QR::Response)
+            .authorative(true)
+            .recursion_available(true)
+            .response_code(ResponseCode::BadCookie)
+            .question(Name::from_str("www.example.com").unwrap(), ResourceType::A, ResourceClass::IN)
+            .question(Name::from_str("version.bind").unwrap(), ResourceType::TXT, ResourceClass::CH)
+            .answer(a)
+            .edns_option(EdnsOption::new(10, vec![1, 2, 3, 4, 5, 6, 7, 8]))
+            .dnssec_ok(true)
+            .build();
+        assert_eq!(message.question_count, 2);
+        assert_eq!(message.question_record[1].query_class, ResourceClass::CH);
+        assert_eq!(message.answer_pr_count, 1);
+        assert_eq!(message.authorative_pr_count, 0);
+        assert_eq!(message.additional_pr_count, 1);
+        let opt = &message.additional_record[0];
+        assert_eq!(opt.rtype, ResourceType::OPT);
+        assert_eq!(opt.rclass, ResourceClass::Unknown(4096));
+        assert_eq!(opt.ttl, 0x01008000);
+
+        let decoded = Message::decode(&encoder::encode(&message).unwrap()).unwrap();
+        assert_eq!(decoded, message);
+
+        // without EDNS there is no OPT record
+        let query = MessageBuilder::new(0)
+            .question(Name::from_str("example.com").unwrap(), ResourceType::A, ResourceClass::IN)
+            .build();
+        assert_eq!(query.additional_pr_count, 0);
+        assert!(!query.flag.recursion_desired);
+
+        // an OPT record from the caller gives way to the builder's own
+        let stale = || Resource {
+            name: Name::root(),
+            rtype: ResourceType::OPT,
+            rclass: ResourceClass::Unknown(512),
+            ttl: 0,
+            rdata: RData::OPT(OPTData::new(Vec::new())),
+        };
+        let message = MessageBuilder::new(0)
+            .additional(stale())
+            .edns(1232)
+            .build();
+        assert_eq!(message.additional_pr_count, 1);
+        assert_eq!(message.additional_record[0].rclass, ResourceClass::Unknown(1232));
+        // without EDNS it is kept as it is
+        let message = MessageBuilder::new(0).additional(stale()).build();
+        assert_eq!(message.additional_record, vec![stale()]);
+    }
 }
@@ -0,0 +1,378 @@
+use message::{Flag, ResponseCode, QuestionRecord};
+use resource::{Resource, ResourceType, ResourceClass, RData};
+use binary::decoder::{Decoder, DecodeResult, Decodable};
+use name::Name;
+
+/// Size of the fixed header that precedes the question section.
+const HEADER_LENGTH: usize = 12;
+
+/// A borrowed view of a message. Only the header is decoded up front; the
+/// sections are walked on demand and names and RDATA are decoded only when
+/// asked for, with the same checks as `Message::decode`.
+#[derive(Debug)]
+pub struct MessageRef<'a> {
+    data: &'a [u8],
+    pub identity             : u16,
+    pub flag                 : Flag,
+    pub question_count       : u16,
+    pub answer_pr_count      : u16,
+    pub authorative_pr_count : u16,
+    pub additional_pr_count  : u16,
+}
+
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Section {
+    Answer,
+    Authority,
+    Additional,
+}
+
+impl<'a> MessageRef<'a> {
+    pub fn new(data: &'a [u8]) -> DecodeResult<MessageRef<'a>> {
+        let mut decoder = Decoder::new(data);
+        let id = try!(decoder.read_u16());
+        let flag = try!(Flag::decode(&mut decoder));
+        Ok(MessageRef {
+            data: data,
+            identity: id,
+            flag: flag,
+            question_count: try!(decoder.read_u16()),
+            answer_pr_count: try!(decoder.read_u16()),
+            authorative_pr_count: try!(decoder.read_u16()),
+            additional_pr_count: try!(decoder.read_u16()),
+        })
+    }
+
+    pub fn questions(&self) -> Questions<'a> {
+        let mut decoder = Decoder::new(self.data);
+        decoder.seek(HEADER_LENGTH);
+        Questions {
+            decoder: decoder,
+            remaining: self.question_count,
+        }
+    }
+
+    /// Iterates over the records of all three sections in order.
+    pub fn records(&self) -> Records<'a> {
+        let mut decoder = Decoder::new(self.data);
+        decoder.seek(HEADER_LENGTH);
+        Records {
+            decoder: decoder,
+            questions: self.question_count,
+            counts: [self.answer_pr_count, self.authorative_pr_count, self.additional_pr_count],
+            index: 0,
+            only: None,
+            failed: false,
+        }
+    }
+    pub fn answers(&self) -> Records<'a> {
+        self.section(Section::Answer)
+    }
+    pub fn authorities(&self) -> Records<'a> {
+        self.section(Section::Authority)
+    }
+    pub fn additionals(&self) -> Records<'a> {
+        self.section(Section::Additional)
+    }
+    fn section(&self, section: Section) -> Records<'a> {
+        let mut records = self.records();
+        records.only = Some(section);
+        records
+    }
+
+    /// The response code including the upper bits from an OPT record, which
+    /// requires walking to the additional section.
+    pub fn response_code(&self) -> DecodeResult<ResponseCode> {
+        for record in self.additionals() {
+            let record = try!(record);
+            if record.rtype == ResourceType::OPT {
+                return Ok(self.flag.response_code.extend(record.ttl));
+            }
+        }
+        Ok(self.flag.response_code)
+    }
+}
+
+#[derive(Debug)]
+pub struct QuestionRef<'a> {
+    data: &'a [u8],
+    name_offset: usize,
+    pub query_type: ResourceType,
+    pub query_class: ResourceClass,
+}
+
+impl<'a> QuestionRef<'a> {
+    pub fn name(&self) -> DecodeResult<Name> {
+        let mut decoder = Decoder::new(self.data);
+        decoder.seek(self.name_offset);
+        decoder.read_name()
+    }
+    pub fn to_question_record(&self) -> DecodeResult<QuestionRecord> {
+        Ok(QuestionRecord {
+            domain_name: try!(self.name()),
+            query_type: self.query_type,
+            query_class: self.query_class,
+        })
+    }
+}
+
+pub struct Questions<'a> {
+    decoder: Decoder<'a>,
+    remaining: u16,
+}
+
+impl<'a> Iterator for Questions<'a> {
+    type Item = DecodeResult<QuestionRef<'a>>;
+
+    fn next(&mut self) -> Option<DecodeResult<QuestionRef<'a>>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let result = read_question(&mut self.decoder);
+        // stop after an error, the position is no longer meaningful
+        self.remaining = if result.is_ok() { self.remaining - 1 } else { 0 };
+        Some(result)
+    }
+}
+
+fn read_question<'a>(decoder: &mut Decoder<'a>) -> DecodeResult<QuestionRef<'a>> {
+    let name_offset = decoder.position();
+    try!(decoder.skip_name());
+    Ok(QuestionRef {
+        data: decoder.data(),
+        name_offset: name_offset,
+        query_type: try!(ResourceType::decode(decoder)),
+        query_class: try!(ResourceClass::decode(decoder)),
+    })
+}
+
+#[derive(Debug)]
+pub struct RecordRef<'a> {
+    data: &'a [u8],
+    name_offset: usize,
+    rdata_offset: usize,
+    rdata_length: usize,
+    pub section: Section,
+    pub rtype: ResourceType,
+    pub rclass: ResourceClass,
+    pub ttl: u32,
+}
+
+impl<'a> RecordRef<'a> {
+    pub fn name(&self) -> DecodeResult<Name> {
+        let mut decoder = Decoder::new(self.data);
+        decoder.seek(self.name_offset);
+        decoder.read_name()
+    }
+    /// The RDATA as it is on the wire; names in it may be compressed.
+    pub fn rdata_bytes(&self) -> &'a [u8] {
+        &self.data[self.rdata_offset..self.rdata_offset + self.rdata_length]
+    }
+    pub fn rdata(&self) -> DecodeResult<RData> {
+        let rtype = self.rtype;
+        let mut decoder = Decoder::new(self.data);
+        decoder.seek(self.rdata_offset);
+        decoder.read_bounded(self.rdata_length, |d| RData::decode(rtype, d))
+    }
+    pub fn to_resource(&self) -> DecodeResult<Resource> {
+        Ok(Resource {
+            name: try!(self.name()),
+            rtype: self.rtype,
+            rclass: self.rclass,
+            ttl: self.ttl,
+            rdata: try!(self.rdata()),
+        })
+    }
+}
+
+pub struct Records<'a> {
+    decoder: Decoder<'a>,
+    // questions still to be skipped before the first record
+    questions: u16,
+    counts: [u16; 3],
+    index: usize,
+    only: Option<Section>,
+    failed: bool,
+}
+
+impl<'a> Records<'a> {
+    fn next_record(&mut self) -> Option<DecodeResult<RecordRef<'a>>> {
+        while self.questions > 0 {
+            if let Err(e) = read_question(&mut self.decoder) {
+                return Some(Err(e));
+            }
+            self.questions = self.questions - 1;
+        }
+        // a single section ends where the next one starts
+        let end = match self.only {
+            Some(Section::Answer) => 1,
+            Some(Section::Authority) => 2,
+            _ => 3,
+        };
+        while self.index < end && self.counts[self.index] == 0 {
+            self.index = self.index + 1;
+        }
+        if self.index == end {
+            return None;
+        }
+        self.counts[self.index] = self.counts[self.index] - 1;
+        let section = match self.index {
+            0 => Section::Answer,
+            1 => Section::Authority,
+            _ => Section::Additional,
+        };
+        Some(read_record(&mut self.decoder, section))
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = DecodeResult<RecordRef<'a>>;
+
+    fn next(&mut self) -> Option<DecodeResult<RecordRef<'a>>> {
+        while !self.failed {
+            match self.next_record() {
+                None => return None,
+                Some(Err(e)) => {
+                    // stop after an error, the position is no longer meaningful
+                    self.failed = true;
+                    return Some(Err(e));
+                },
+                Some(Ok(record)) => {
+                    if self.only.map_or(true, |s| s == record.section) {
+                        return Some(Ok(record));
+                    }
+                },
+            }
+        }
+        None
+    }
+}
+
+fn read_record<'a>(decoder: &mut Decoder<'a>, section: Section) -> DecodeResult<RecordRef<'a>> {
+    let name_offset = decoder.position();
+    try!(decoder.skip_name());
+    let rtype = try!(ResourceType::decode(decoder));
+    let rclass = try!(ResourceClass::decode(decoder));
+    let ttl = try!(decoder.read_u32());
+    let rdata_length = try!(decoder.read_u16()) as usize;
+    let rdata_offset = decoder.position();
+    try!(decoder.skip(rdata_length));
+    Ok(RecordRef {
+        data: decoder.data(),
+        name_offset: name_offset,
+        rdata_offset: rdata_offset,
+        rdata_length: rdata_length,
+        section: section,
+        rtype: rtype,
+        rclass: rclass,
+        ttl: ttl,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MessageRef, Section};
+    use message::{Message, ResponseCode, Operation};
+    use resource::{ResourceType, ResourceClass, RData};
+    use binary::decoder::DecodeError;
+    use name::Name;
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    const RESPONSE: [u8; 73] = [
+        0x12, 0x34, 0x81, 0x80, 0u8, 1u8, 0u8, 2u8, 0u8, 0u8, 0u8, 1u8,
+        // question google.com IN A
+        6u8, 103u8, 111u8, 111u8, 103u8, 108u8, 101u8,
+        3u8, 99u8, 111u8, 109u8, 0u8,
+        0u8, 1u8, 0u8, 1u8,
+        // www.google.com CNAME google.com
+        3u8, 119u8, 119u8, 119u8, 0xc0, 0x0c,
+        0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0x63, 0x00, 0x02,
+        0xc0, 0x0c,
+        // google.com A 173.194.126.193
+        0xc0, 0x0c,
+        0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x63, 0x00, 0x04,
+        0xad, 0xc2, 0x7e, 0xc1,
+        // OPT with extended rcode 1
+        0u8, 0x00, 0x29, 0x10, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_header() {
+        let message = MessageRef::new(&RESPONSE).ok().unwrap();
+        assert_eq!(message.identity, 0x1234);
+        assert_eq!(message.flag.response_code, ResponseCode::NoError);
+        assert_eq!(message.answer_pr_count, 2);
+        assert_eq!(message.response_code(), Ok(ResponseCode::BadVersion));
+        assert_eq!(MessageRef::new(&RESPONSE[..11]).err(), Some(DecodeError::Truncated { offset: 10, needed: 2 }));
+
+        // opcodes without a dedicated decoder still give a readable header
+        let mut dso = RESPONSE;
+        dso[2] = 0x30;
+        assert_eq!(MessageRef::new(&dso).ok().unwrap().flag.operation, Operation::DSO);
+    }
+
+    #[test]
+    fn test_sections() {
+        let message = MessageRef::new(&RESPONSE).ok().unwrap();
+        let questions: Vec<_> = message.questions().map(|q| q.ok().unwrap()).collect();
+        assert_eq!(questions.len(), 1);
+        assert_eq!(questions[0].name().map(|n| n.to_string()), Ok("google.com.".to_string()));
+        assert_eq!(questions[0].query_class, ResourceClass::IN);
+
+        let answers: Vec<_> = message.answers().map(|r| r.ok().unwrap()).collect();
+        assert_eq!(answers.len(), 2);
+        assert_eq!(answers[0].name().map(|n| n.to_string()), Ok("www.google.com.".to_string()));
+        assert_eq!(answers[0].rtype, ResourceType::CNAME);
+        assert_eq!(answers[0].rdata_bytes(), &[0xc0, 0x0c]);
+        assert_eq!(answers[0].rdata(), Ok(RData::CNAME(Name::from_str("google.com").unwrap())));
+        assert_eq!(answers[1].rdata(), Ok(RData::A(Ipv4Addr::new(173, 194, 126, 193))));
+
+        let additionals: Vec<_> = message.additionals().map(|r| r.ok().unwrap()).collect();
+        assert_eq!(additionals.len(), 1);
+        assert_eq!(additionals[0].section, Section::Additional);
+        assert_eq!(message.authorities().count(), 0);
+        assert_eq!(message.records().count(), 3);
+    }
+
+    #[test]
+    fn test_matches_decode() {
+        let message = MessageRef::new(&RESPONSE).ok().unwrap();
+        let decoded = Message::decode(&RESPONSE).ok().unwrap();
+        let answers: Vec<_> = message.answers().map(|r| r.and_then(|r| r.to_resource()).ok().unwrap()).collect();
+        assert_eq!(answers, decoded.answer_record);
+        let question = message.questions().next().unwrap().and_then(|q| q.to_question_record());
+        assert_eq!(question.ok().unwrap(), decoded.question_record[0]);
+    }
+
+    #[test]
+    fn test_lazy_errors() {
+        // the header is fine, the answer is cut short
+        let message = MessageRef::new(&RESPONSE[..50]).ok().unwrap();
+        assert_eq!(message.questions().count(), 1);
+        let mut answers = message.answers();
+        assert!(answers.next().unwrap().is_ok());
+        assert_eq!(answers.next().unwrap().err(), Some(DecodeError::Truncated { offset: 50, needed: 2 }));
+        assert!(answers.next().is_none());
+
+        // the answers are fine, the additional section is cut short
+        let message = MessageRef::new(&RESPONSE[..70]).ok().unwrap();
+        let answers: Vec<_> = message.answers().collect();
+        assert_eq!(answers.len(), 2);
+        assert!(answers.iter().all(|r| r.is_ok()));
+        assert_eq!(message.authorities().count(), 0);
+        let mut additionals = message.additionals();
+        assert_eq!(additionals.next().unwrap().err(), Some(DecodeError::Truncated { offset: 67, needed: 4 }));
+        assert!(additionals.next().is_none());
+
+        // a pointer loop in the question is reported like Message::decode does
+        let looped = [
+            0u8, 0u8, 1u8, 0u8, 0u8, 1u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8,
+            0xc0, 0x0c, 0u8, 1u8, 0u8, 1u8,
+        ];
+        let message = MessageRef::new(&looped).ok().unwrap();
+        let error = message.questions().next().unwrap().err();
+        assert_eq!(error, Message::decode(&looped).err());
+        assert_eq!(error, Some(DecodeError::PointerLoop { offset: 12, target: 12 }));
+    }
+}
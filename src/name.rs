@@ -0,0 +1,409 @@
+use std::result::Result;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use binary::encoder::EncodeError;
+
+/// Maximum length of a domain name in wire format (RFC 1035 3.1).
+pub const MAX_NAME_LENGTH: usize = 255;
+/// Maximum length of a single label.
+pub const MAX_LABEL_LENGTH: usize = 63;
+
+/// Splits a name in RFC 1035 presentation format into its raw labels.
+///
+/// `\.` and `\\` escape the next character and `\DDD` stands for the octet
+/// with decimal value DDD, so any label can be written out. The root is
+/// either the empty string or a single `.`. Labels and the name as a whole
+/// have to fit their limits in wire format.
+pub fn parse(v: &str) -> Result<Vec<Vec<u8>>, EncodeError> {
+    parse_name(v).map(|(labels, _)| labels)
+}
+
+/// Like `parse`, also telling whether the name ended in an unescaped dot.
+fn parse_name(v: &str) -> Result<(Vec<Vec<u8>>, bool), EncodeError> {
+    let bytes = v.as_bytes();
+    let mut trailing_dot = false;
+    let mut labels = Vec::new();
+    let mut label = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'.' => {
+                if label.is_empty() {
+                    if bytes.len() == 1 {
+                        trailing_dot = true;
+                        break;
+                    }
+                    return Err(EncodeError::EmptyLabel { name: v.to_string() });
+                }
+                labels.push(label);
+                label = Vec::new();
+                i = i + 1;
+                trailing_dot = i == bytes.len();
+            },
+            b'\\' => {
+                if i + 1 >= bytes.len() {
+                    return Err(EncodeError::BadEscape { name: v.to_string() });
+                }
+                if is_digit(bytes[i + 1]) {
+                    if i + 4 > bytes.len() || !is_digit(bytes[i + 2]) || !is_digit(bytes[i + 3]) {
+                        return Err(EncodeError::BadEscape { name: v.to_string() });
+                    }
+                    let n = (bytes[i + 1] - b'0') as u32 * 100
+                          + (bytes[i + 2] - b'0') as u32 * 10
+                          + (bytes[i + 3] - b'0') as u32;
+                    if n > 255 {
+                        return Err(EncodeError::BadEscape { name: v.to_string() });
+                    }
+                    label.push(n as u8);
+                    i = i + 4;
+                } else {
+                    label.push(bytes[i + 1]);
+                    i = i + 2;
+                }
+            },
+            b => {
+                label.push(b);
+                i = i + 1;
+            },
+        }
+    }
+    if !label.is_empty() {
+        labels.push(label);
+    }
+    try!(check_lengths(&labels, v));
+    Ok((labels, trailing_dot))
+}
+
+fn check_lengths(labels: &[Vec<u8>], name: &str) -> Result<(), EncodeError> {
+    let mut length = 1;
+    for label in labels {
+        if label.len() > MAX_LABEL_LENGTH {
+            return Err(EncodeError::LabelTooLong { name: name.to_string(), length: label.len() });
+        }
+        if label.is_empty() {
+            return Err(EncodeError::EmptyLabel { name: name.to_string() });
+        }
+        length = length + label.len() + 1;
+    }
+    if length > MAX_NAME_LENGTH {
+        return Err(EncodeError::NameTooLong { name: name.to_string(), length: length });
+    }
+    Ok(())
+}
+
+/// Formats raw labels in RFC 1035 presentation format, escaping everything
+/// that would not read back as the same octets.
+pub fn to_presentation(labels: &[Vec<u8>]) -> String {
+    let escaped: Vec<String> = labels.iter().map(|l| escape_label(l)).collect();
+    escaped.join(".")
+}
+
+/// A domain name, kept as its raw labels.
+///
+/// Names compare, hash and sort case-insensitively (RFC 4343) and in the
+/// canonical order of RFC 4034 6.1. Whether a name is fully qualified
+/// affects only how it is shown, so `example.com` equals `example.com.`.
+#[derive(Clone,Debug)]
+pub struct Name {
+    labels: Vec<Vec<u8>>,
+    fqdn: bool,
+}
+
+impl Name {
+    pub fn root() -> Name {
+        Name {
+            labels: Vec::new(),
+            fqdn: true,
+        }
+    }
+
+    /// Builds a fully qualified name from raw labels, as read off the wire.
+    pub fn from_labels(labels: Vec<Vec<u8>>) -> Result<Name, EncodeError> {
+        try!(check_lengths(&labels, &to_presentation(&labels)));
+        Ok(Name {
+            labels: labels,
+            fqdn: true,
+        })
+    }
+
+    pub fn labels(&self) -> &[Vec<u8>] {
+        &self.labels
+    }
+    pub fn num_labels(&self) -> usize {
+        self.labels.len()
+    }
+    pub fn is_root(&self) -> bool {
+        self.labels.is_empty()
+    }
+    pub fn is_fqdn(&self) -> bool {
+        self.fqdn
+    }
+
+    /// Whether this name is `other` or lies below it.
+    pub fn is_subdomain_of(&self, other: &Name) -> bool {
+        if other.labels.len() > self.labels.len() {
+            return false;
+        }
+        let skip = self.labels.len() - other.labels.len();
+        self.labels[skip..].iter().zip(other.labels.iter()).all(|(a, b)| eq_label(a, b))
+    }
+
+    /// The name with its leftmost label removed, or `None` for the root.
+    pub fn parent(&self) -> Option<Name> {
+        if self.labels.is_empty() {
+            return None;
+        }
+        Some(Name {
+            labels: self.labels[1..].to_vec(),
+            fqdn: self.fqdn,
+        })
+    }
+
+    /// Appends `suffix`, treating this name as relative to it.
+    pub fn append(&self, suffix: &Name) -> Result<Name, EncodeError> {
+        let mut labels = self.labels.clone();
+        labels.extend(suffix.labels.iter().cloned());
+        try!(check_lengths(&labels, &to_presentation(&labels)));
+        Ok(Name {
+            labels: labels,
+            fqdn: suffix.fqdn,
+        })
+    }
+
+    /// Length in wire format, without compression.
+    pub fn wire_length(&self) -> usize {
+        self.labels.iter().fold(1, |n, l| n + l.len() + 1)
+    }
+}
+
+impl FromStr for Name {
+    type Err = EncodeError;
+
+    fn from_str(v: &str) -> Result<Name, EncodeError> {
+        let (labels, trailing_dot) = try!(parse_name(v));
+        let fqdn = labels.is_empty() || trailing_dot;
+        Ok(Name {
+            labels: labels,
+            fqdn: fqdn,
+        })
+    }
+}
+
+impl Display for Name {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        if self.labels.is_empty() {
+            return fmt.write_str(".");
+        }
+        try!(fmt.write_str(&to_presentation(&self.labels)));
+        if self.fqdn {
+            try!(fmt.write_str("."));
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Name) -> bool {
+        self.labels.len() == other.labels.len()
+            && self.labels.iter().zip(other.labels.iter()).all(|(a, b)| eq_label(a, b))
+    }
+}
+
+impl Eq for Name {}
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for label in &self.labels {
+            let lower: Vec<u8> = label.iter().map(|b| lower(*b)).collect();
+            lower.hash(state);
+        }
+    }
+}
+
+impl Ord for Name {
+    /// Canonical DNS name order: labels compared as lowercase octets from
+    /// the rightmost one, with the absence of a label sorting first.
+    fn cmp(&self, other: &Name) -> Ordering {
+        let mut a = self.labels.iter().rev();
+        let mut b = other.labels.iter().rev();
+        loop {
+            match (a.next(), b.next()) {
+                (None, None) => return Ordering::Equal,
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some(x), Some(y)) => {
+                    let x = x.iter().map(|b| lower(*b));
+                    let y = y.iter().map(|b| lower(*b));
+                    match x.cmp(y) {
+                        Ordering::Equal => continue,
+                        ordering => return ordering,
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Name) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn lower(b: u8) -> u8 {
+    if b >= b'A' && b <= b'Z' { b + 32 } else { b }
+}
+
+fn eq_label(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| lower(*x) == lower(*y))
+}
+
+fn escape_label(label: &[u8]) -> String {
+    let mut s = String::new();
+    for &b in label {
+        match b {
+            b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                s.push('\\');
+                s.push(b as char);
+            },
+            b if b >= 0x21 && b <= 0x7e => s.push(b as char),
+            _ => s.push_str(&format!("\\{:03}", b)),
+        }
+    }
+    s
+}
+
+fn is_digit(b: u8) -> bool {
+    b >= b'0' && b <= b'9'
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, to_presentation, Name};
+    use binary::encoder::EncodeError;
+    use std::collections::HashSet;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("www.example.com"), Ok(vec![b"www".to_vec(), b"example".to_vec(), b"com".to_vec()]));
+        assert_eq!(parse("example.com."), Ok(vec![b"example".to_vec(), b"com".to_vec()]));
+        assert_eq!(parse(""), Ok(vec![]));
+        assert_eq!(parse("."), Ok(vec![]));
+        assert_eq!(parse("a\\.b.c"), Ok(vec![b"a.b".to_vec(), b"c".to_vec()]));
+        assert_eq!(parse("a\\\\b"), Ok(vec![b"a\\b".to_vec()]));
+        assert_eq!(parse("\\032\\255x\\000"), Ok(vec![vec![32, 255, b'x', 0]]));
+        assert!(parse("a..b").is_err());
+        assert!(parse(".a").is_err());
+        assert!(parse("a\\").is_err());
+        assert!(parse("a\\25").is_err());
+        assert!(parse("a\\256").is_err());
+        assert!(parse(&"a".repeat(63)).is_ok());
+        assert!(parse(&"a".repeat(64)).is_err());
+        assert!(parse(&"\\046".repeat(63)).is_ok());
+    }
+
+    #[test]
+    fn test_to_presentation() {
+        assert_eq!(to_presentation(&[]), "");
+        assert_eq!(to_presentation(&[b"example".to_vec(), b"com".to_vec()]), "example.com");
+        assert_eq!(to_presentation(&[b"a.b".to_vec(), b"c".to_vec()]), "a\\.b.c");
+        assert_eq!(to_presentation(&[vec![b'a', b' ', 0xe4, 0, b'\\']]), "a\\032\\228\\000\\\\");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let labels = vec![(0u8..63).collect::<Vec<u8>>(), (192u8..255).collect::<Vec<u8>>(), b"@$;()\"".to_vec()];
+        assert_eq!(parse(&to_presentation(&labels)), Ok(labels));
+    }
+
+
+    #[test]
+    fn test_name_from_str() {
+        assert!(Name::from_str("example.com.").unwrap().is_fqdn());
+        assert!(!Name::from_str("example.com").unwrap().is_fqdn());
+        assert!(!Name::from_str("a\\.").unwrap().is_fqdn());
+        assert!(Name::from_str("a\\\\.").unwrap().is_fqdn());
+        assert!(Name::from_str(".").unwrap().is_root());
+        assert_eq!(Name::from_str("www.example.com").unwrap().num_labels(), 3);
+        assert_eq!(Name::from_str("a\\.b.c").unwrap().labels()[0], b"a.b".to_vec());
+        assert_eq!("a..b".parse::<Name>().err(), Some(EncodeError::EmptyLabel { name: "a..b".to_string() }));
+        assert_eq!("a\\999".parse::<Name>().err(), Some(EncodeError::BadEscape { name: "a\\999".to_string() }));
+        let long_label = "a".repeat(64);
+        assert_eq!(long_label.parse::<Name>().err(), Some(EncodeError::LabelTooLong { name: long_label.clone(), length: 64 }));
+        let long_name = vec!["a".repeat(63); 4].join(".");
+        assert_eq!(long_name.parse::<Name>().err(), Some(EncodeError::NameTooLong { name: long_name.clone(), length: 257 }));
+    }
+
+    #[test]
+    fn test_name_display() {
+        assert_eq!(Name::from_str("Example.COM.").unwrap().to_string(), "Example.COM.");
+        assert_eq!(Name::from_str("example.com").unwrap().to_string(), "example.com");
+        assert_eq!(Name::root().to_string(), ".");
+        assert_eq!(Name::from_labels(vec![b"a b".to_vec()]).ok().unwrap().to_string(), "a\\032b.");
+    }
+
+    #[test]
+    fn test_name_equality() {
+        assert_eq!(Name::from_str("WWW.Example.com").unwrap(), Name::from_str("www.example.COM.").unwrap());
+        assert!(Name::from_str("www.example.com").unwrap() != Name::from_str("www.example.org").unwrap());
+        assert!(Name::from_str("a\\.b").unwrap() != Name::from_str("a.b").unwrap());
+        let mut set = HashSet::new();
+        set.insert(Name::from_str("Example.com").unwrap());
+        assert!(set.contains(&Name::from_str("example.COM.").unwrap()));
+    }
+
+    #[test]
+    fn test_name_relations() {
+        let zone = Name::from_str("example.com.").unwrap();
+        assert!(Name::from_str("www.EXAMPLE.com.").unwrap().is_subdomain_of(&zone));
+        assert!(zone.is_subdomain_of(&zone));
+        assert!(!Name::from_str("example.org.").unwrap().is_subdomain_of(&zone));
+        assert!(!Name::from_str("com.").unwrap().is_subdomain_of(&zone));
+        assert!(!Name::from_str("wwwexample.com.").unwrap().is_subdomain_of(&zone));
+        assert!(zone.is_subdomain_of(&Name::root()));
+
+        assert_eq!(Name::from_str("www.example.com.").unwrap().parent(), Some(zone.clone()));
+        assert_eq!(Name::from_str("com.").unwrap().parent(), Some(Name::root()));
+        assert_eq!(Name::root().parent(), None);
+
+        let www = Name::from_str("www").unwrap().append(&zone).ok().unwrap();
+        assert_eq!(www, Name::from_str("www.example.com.").unwrap());
+        assert!(www.is_fqdn());
+        assert_eq!(www.wire_length(), 17);
+        let long = Name::from_labels(vec![vec![b'a'; 63]; 3]).ok().unwrap();
+        match long.append(&long) {
+            Err(EncodeError::NameTooLong { .. }) => {},
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_name_canonical_order() {
+        // the example from RFC 4034 6.1
+        let ordered = vec![
+            Name::from_str("example.").unwrap(),
+            Name::from_str("a.example.").unwrap(),
+            Name::from_str("yljkjljk.a.example.").unwrap(),
+            Name::from_str("Z.a.example.").unwrap(),
+            Name::from_str("zABC.a.EXAMPLE.").unwrap(),
+            Name::from_str("z.example.").unwrap(),
+            Name::from_str("\\001.z.example.").unwrap(),
+            Name::from_str("*.z.example.").unwrap(),
+            Name::from_str("\\200.z.example.").unwrap(),
+        ];
+        let mut shuffled = vec![
+            ordered[5].clone(), ordered[8].clone(), ordered[0].clone(),
+            ordered[3].clone(), ordered[7].clone(), ordered[1].clone(),
+            ordered[6].clone(), ordered[2].clone(), ordered[4].clone(),
+        ];
+        shuffled.sort();
+        assert_eq!(shuffled, ordered);
+        for (a, b) in shuffled.iter().zip(ordered.iter()) {
+            assert_eq!(a.to_string(), b.to_string());
+        }
+    }
+}
@@ -1,22 +1,37 @@
-use std::net::{SocketAddrV4, UdpSocket, Ipv4Addr};
-use message::{Message, DecodeError, Operation};
-use resource::ResourceType;
+use std::net::{SocketAddr, SocketAddrV4, UdpSocket, Ipv4Addr};
+use message::{Message, Operation, QR};
+use message_ref::MessageRef;
+use resource::{ResourceType, ResourceClass};
+use name::Name;
 use std::fs::File;
 use std::io::Read;
+use std::process;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::str::FromStr;
 use regex::Regex;
 use binary::encoder;
 
+/// How long to wait for a matching response from each server.
+const TIMEOUT_SECS: u64 = 5;
+
 pub struct Resolver {
     name_servers: Vec<Ipv4Addr>,
+    randomize_case: bool,
 }
 
 impl Resolver {
     pub fn new(name_servers: Vec<Ipv4Addr>) -> Resolver {
         Resolver{
             name_servers: name_servers,
+            randomize_case: false,
         }
     }
+    /// Randomizes the case of the letters in each query name ("DNS 0x20")
+    /// and only accepts responses that echo it exactly.
+    pub fn randomize_case(mut self, enabled: bool) -> Resolver {
+        self.randomize_case = enabled;
+        self
+    }
     pub fn from_reolv_conf() -> Resolver {
         let ns = Resolver::parse_resolv_conf();
         Resolver::new(ns)
@@ -42,17 +57,23 @@ impl Resolver {
         return nameservers;
     }
     pub fn resolve(&self,
-               name: String,
-               resource_type: ResourceType) -> Result<Message, String> {
+               name: Name,
+               resource_type: ResourceType,
+               resource_class: ResourceClass) -> Result<Message, String> {
         let local = SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0);
         for ns in self.name_servers.iter() {
-            let name = name.clone();
+            let name = if self.randomize_case {
+                randomize_case(&name)
+            } else {
+                name.clone()
+            };
             let query = Message::new(
-                0,
+                random_id(),
                 Operation::StandardQuery,
                 true,
                 vec![name],
                 resource_type,
+                resource_class,
             );
             let socket = match UdpSocket::bind(local) {
                 Ok(sock) => sock,
@@ -62,9 +83,12 @@ impl Resolver {
                 }
             };
 
-            let mut remote = ns.to_string();
-            remote.push_str(":53");
-            match socket.send_to(encoder::encode(&query).ok().unwrap().as_slice(), remote.as_str()) {
+            let encoded = match encoder::encode(&query) {
+                Ok(v) => v,
+                Err(e) => return Err(e.to_string()),
+            };
+            let server = SocketAddr::V4(SocketAddrV4::new(*ns, 53));
+            match socket.send_to(encoded.as_slice(), server) {
                 Ok(_) => {},
                 Err(e) => {
                     println!("Error {}", e);
@@ -72,26 +96,117 @@ impl Resolver {
                 }
             };
 
+            // anyone can send us datagrams, so keep waiting until one from
+            // the server answers our query
+            let deadline = Instant::now() + Duration::from_secs(TIMEOUT_SECS);
             let mut buf = [0; 1024];
-            let res = socket.recv_from(&mut buf);
-            let (len, _) = res.unwrap();
-
-            let response: &[u8] = &buf[0..len];
-            return match Message::decode(response) {
-                Ok(v) => Ok(v),
-                Err(DecodeError::InvalidFormatErr(s)) => Err(s.to_string()),
+            loop {
+                let now = Instant::now();
+                if now >= deadline {
+                    println!("Timed out waiting for {}", server);
+                    break;
+                }
+                if let Err(e) = socket.set_read_timeout(Some(deadline - now)) {
+                    println!("Error {}", e);
+                    break;
+                }
+                let (len, from) = match socket.recv_from(&mut buf) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        println!("Error {}", e);
+                        break;
+                    }
+                };
+                let response: &[u8] = &buf[0..len];
+                if from != server || !is_response_to(&query, response, self.randomize_case) {
+                    continue;
+                }
+                return match Message::decode(response) {
+                    Ok(v) => Ok(v),
+                    Err(e) => Err(e.to_string()),
+                }
             }
         }
         Err("Failed to resolve".to_string())
     }
 }
 
+/// Whether `response` answers `query`: it has to be a response with the
+/// same ID and the same questions. With `exact_case` the names have to
+/// match octet for octet.
+fn is_response_to(query: &Message, response: &[u8], exact_case: bool) -> bool {
+    let message = match MessageRef::new(response) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+    if message.identity != query.identity
+        || message.flag.query_or_response != QR::Response
+        || message.question_count as usize != query.question_record.len() {
+        return false;
+    }
+    message.questions().zip(query.question_record.iter()).all(|(question, expected)| {
+        match question.and_then(|q| q.to_question_record()) {
+            Ok(q) => q == *expected
+                && (!exact_case || q.domain_name.labels() == expected.domain_name.labels()),
+            Err(_) => false,
+        }
+    })
+}
+
+/// Flips the case of each letter in `name` at random.
+fn randomize_case(name: &Name) -> Name {
+    let mut random = vec![0u8; name.wire_length()];
+    random_bytes(&mut random);
+    flip_case(name, &random)
+}
+
+/// Flips the case of the letters in `name` whose octet, counted across all
+/// labels, has the lowest bit set in `random`.
+fn flip_case(name: &Name, random: &[u8]) -> Name {
+    let mut bits = random.iter();
+    let labels = name.labels().iter().map(|label| {
+        label.iter().map(|&b| {
+            let flip = bits.next().map_or(false, |r| r & 1 == 1);
+            if flip && (b as char).is_ascii_alphabetic() { b ^ 0x20 } else { b }
+        }).collect()
+    }).collect();
+    Name::from_labels(labels).unwrap()
+}
+
+fn random_id() -> u16 {
+    let mut buf = [0u8; 2];
+    random_bytes(&mut buf);
+    ((buf[0] as u16) << 8) | buf[1] as u16
+}
+
+/// Fills `buf` from /dev/urandom, or from a generator seeded with the clock
+/// if it cannot be read.
+fn random_bytes(buf: &mut [u8]) {
+    if let Ok(mut file) = File::open("/dev/urandom") {
+        if file.read_exact(buf).is_ok() {
+            return;
+        }
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0));
+    let mut x = ((now.subsec_nanos() as u64) << 32) ^ now.as_secs() ^ process::id() as u64 | 1;
+    for b in buf.iter_mut() {
+        // xorshift64
+        x = x ^ (x << 13);
+        x = x ^ (x >> 7);
+        x = x ^ (x << 17);
+        *b = (x >> 24) as u8;
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Resolver;
+    use super::{Resolver, is_response_to, randomize_case, flip_case};
+    use message::{Message, MessageBuilder, QR};
+    use binary::encoder;
     use std::net::Ipv4Addr;
     use std::str::FromStr;
     use std::ops::Index;
+    use name::Name;
     use resource::{ResourceType, ResourceClass, Resource, RData};
 
     #[test]
@@ -99,13 +214,13 @@ mod tests {
         let ns = Resolver::parse_resolv_conf();
         let resolver = Resolver::new(ns);
         let expected = Resource {
-            name: "localhost".to_string(),
+            name: Name::from_str("localhost").unwrap(),
             rtype: ResourceType::A,
             rclass: ResourceClass::IN,
             ttl: 600,
             rdata: RData::A(Ipv4Addr::new(127, 0, 0, 1)),
         };
-        match resolver.resolve("localhost".to_string(), ResourceType::A) {
+        match resolver.resolve(Name::from_str("localhost").unwrap(), ResourceType::A, ResourceClass::IN) {
             Ok(message) => {
                 assert_eq!(*message.answer_record.index(0), expected);
             },
@@ -115,4 +230,57 @@ mod tests {
             }
         };
     }
+
+    #[test]
+    fn test_is_response_to() {
+        let name = Name::from_str("example.com").unwrap();
+        let query = MessageBuilder::new(0x1234)
+            .question(name.clone(), ResourceType::A, ResourceClass::IN)
+            .build();
+        let response = |id: u16, qr: QR, rtype: ResourceType| {
+            let message: Message = MessageBuilder::new(id)
+                .query_or_response(qr)
+                .question(name.clone(), rtype, ResourceClass::IN)
+                .build();
+            encoder::encode(&message).unwrap()
+        };
+        assert!(is_response_to(&query, &response(0x1234, QR::Response, ResourceType::A), false));
+        assert!(!is_response_to(&query, &response(0x1235, QR::Response, ResourceType::A), false));
+        assert!(!is_response_to(&query, &response(0x1234, QR::Query, ResourceType::A), false));
+        assert!(!is_response_to(&query, &response(0x1234, QR::Response, ResourceType::AAAA), false));
+        assert!(!is_response_to(&query, &[0x12, 0x34, 0x80], false));
+
+        let other = MessageBuilder::new(0x1234)
+            .query_or_response(QR::Response)
+            .question(Name::from_str("example.org").unwrap(), ResourceType::A, ResourceClass::IN)
+            .build();
+        assert!(!is_response_to(&query, &encoder::encode(&other).unwrap(), false));
+    }
+
+    #[test]
+    fn test_randomize_case() {
+        let name = Name::from_str("www-1.example.com").unwrap();
+        let randomized = randomize_case(&name);
+        assert_eq!(randomized, name);
+        assert_eq!(randomized.labels()[0][3..], name.labels()[0][3..]);
+        let bits: Vec<u8> = (0..20).map(|i| if i % 2 == 0 { 0xff } else { 0xfe }).collect();
+        assert_eq!(flip_case(&name, &bits).to_string(), "WwW-1.eXaMpLe.CoM.");
+        assert_eq!(flip_case(&name, &[1u8; 20]).to_string(), "WWW-1.EXAMPLE.COM.");
+        assert_eq!(flip_case(&flip_case(&name, &bits), &bits).to_string(), "www-1.example.com.");
+        assert_eq!(flip_case(&name, &[]).to_string(), "www-1.example.com.");
+
+        let query = MessageBuilder::new(1)
+            .question(Name::from_str("ExAmPlE.cOm").unwrap(), ResourceType::A, ResourceClass::IN)
+            .build();
+        let response = |v: &str| {
+            let message = MessageBuilder::new(1)
+                .query_or_response(QR::Response)
+                .question(Name::from_str(v).unwrap(), ResourceType::A, ResourceClass::IN)
+                .build();
+            encoder::encode(&message).unwrap()
+        };
+        assert!(is_response_to(&query, &response("ExAmPlE.cOm"), true));
+        assert!(!is_response_to(&query, &response("example.com"), true));
+        assert!(is_response_to(&query, &response("example.com"), false));
+    }
 }
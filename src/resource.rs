@@ -1,4 +1,7 @@
 use num::traits::FromPrimitive;
+use binary::encoder::{Encoder, EncodeResult, EncodeError, Encodable};
+use binary::decoder::{Decoder, DecodeResult, DecodeError, Decodable};
+use name::Name;
 
 use std::net::{Ipv4Addr, Ipv6Addr};
 use std::fmt;
@@ -6,17 +9,60 @@ use std::fmt::{Display, Formatter};
 
 #[derive(Debug,PartialEq)]
 pub struct Resource {
-    pub name: String,
+    pub name: Name,
     pub rtype: ResourceType,
     pub rclass: ResourceClass,
     pub ttl: u32,
     pub rdata: RData,
 }
 
+impl Encodable for Resource {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        self.encode_with_ttl(encoder, self.ttl)
+    }
+}
+
+impl Resource {
+    /// Encodes the record with `ttl` in place of its own, which lets the
+    /// message fill in the OPT TTL from its header.
+    pub fn encode_with_ttl(&self, encoder: &mut Encoder, ttl: u32) -> EncodeResult<()> {
+        try!(encoder.emit_name(&self.name));
+        try!(encoder.emit_u16(self.rtype.to_u16()));
+        try!(encoder.emit_u16(self.rclass.to_u16()));
+        try!(encoder.emit_u32(ttl));
+        let length_pos = encoder.position();
+        try!(encoder.emit_u16(0));
+        try!(self.rdata.encode(encoder));
+        let length = encoder.position() - length_pos - 2;
+        if length > 0xffff {
+            return Err(EncodeError::RdataTooLong { length: length });
+        }
+        encoder.patch_u16(length_pos, length as u16)
+    }
+}
+
+impl Decodable for Resource {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<Resource> {
+        let name = try!(decoder.read_name());
+        let rtype = try!(ResourceType::decode(decoder));
+        let rclass = try!(ResourceClass::decode(decoder));
+        let ttl = try!(decoder.read_u32());
+        let length = try!(decoder.read_u16()) as usize;
+        let rdata = try!(decoder.read_bounded(length, |d| RData::decode(rtype, d)));
+        Ok(Resource {
+            name: name,
+            rtype: rtype,
+            rclass: rclass,
+            ttl: ttl,
+            rdata: rdata,
+        })
+    }
+}
+
 #[derive(Debug,PartialEq)]
 pub struct SOAData {
-    pub primary_ns: String,
-    pub admin_mb: String,
+    pub primary_ns: Name,
+    pub admin_mb: Name,
     pub serial: u32,
     pub refresh_interval: u32,
     pub retry_interval: u32,
@@ -25,7 +71,7 @@ pub struct SOAData {
 }
 
 impl SOAData {
-    pub fn new(ns: String, mb: String,
+    pub fn new(ns: Name, mb: Name,
                serial: u32, refresh_interval: u32,
                retry_interval: u32, expiration_limit: u32,
                minimal_ttl: u32) -> SOAData {
@@ -55,14 +101,41 @@ impl Display for SOAData {
     }
 }
 
+impl Encodable for SOAData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(encoder.emit_name(&self.primary_ns));
+        try!(encoder.emit_name(&self.admin_mb));
+        try!(encoder.emit_u32(self.serial));
+        try!(encoder.emit_u32(self.refresh_interval));
+        try!(encoder.emit_u32(self.retry_interval));
+        try!(encoder.emit_u32(self.expiration_limit));
+        encoder.emit_u32(self.minimal_ttl)
+    }
+}
+
+impl Decodable for SOAData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<SOAData> {
+        let ns = try!(decoder.read_name());
+        let mb = try!(decoder.read_name());
+        Ok(SOAData::new(
+            ns, mb,
+            try!(decoder.read_u32()),
+            try!(decoder.read_u32()),
+            try!(decoder.read_u32()),
+            try!(decoder.read_u32()),
+            try!(decoder.read_u32())
+        ))
+    }
+}
+
 #[derive(Debug,PartialEq)]
 pub struct MXData {
     pub preference: u16,
-    pub mx: String,
+    pub mx: Name,
 }
 
 impl MXData {
-    pub fn new(pref: u16, mx: String) -> MXData {
+    pub fn new(pref: u16, mx: Name) -> MXData {
         MXData {
             preference: pref,
             mx: mx,
@@ -76,15 +149,802 @@ impl Display for MXData {
     }
 }
 
+impl Encodable for MXData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(encoder.emit_u16(self.preference));
+        encoder.emit_name(&self.mx)
+    }
+}
+
+impl Decodable for MXData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<MXData> {
+        let preference = try!(decoder.read_u16());
+        let mx = try!(decoder.read_name());
+        Ok(MXData::new(preference, mx))
+    }
+}
+
+/// RDATA of HINFO records: host CPU and operating system.
+#[derive(Debug,PartialEq)]
+pub struct HINFOData {
+    pub cpu: Vec<u8>,
+    pub os: Vec<u8>,
+}
+
+impl HINFOData {
+    pub fn new(cpu: Vec<u8>, os: Vec<u8>) -> HINFOData {
+        HINFOData {
+            cpu: cpu,
+            os: os,
+        }
+    }
+}
+
+impl Display for HINFOData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_fmt(format_args!("{} {}", quote(&self.cpu), quote(&self.os)))
+    }
+}
+
+impl Encodable for HINFOData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(encoder.emit_character_string(&self.cpu));
+        encoder.emit_character_string(&self.os)
+    }
+}
+
+impl Decodable for HINFOData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<HINFOData> {
+        let cpu = try!(decoder.read_character_string());
+        let os = try!(decoder.read_character_string());
+        Ok(HINFOData::new(cpu, os))
+    }
+}
+
+/// RDATA of MINFO records: the mailboxes responsible for a mailing list
+/// and for errors about it.
+#[derive(Debug,PartialEq)]
+pub struct MINFOData {
+    pub rmailbx: Name,
+    pub emailbx: Name,
+}
+
+impl MINFOData {
+    pub fn new(rmailbx: Name, emailbx: Name) -> MINFOData {
+        MINFOData {
+            rmailbx: rmailbx,
+            emailbx: emailbx,
+        }
+    }
+}
+
+impl Display for MINFOData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_fmt(format_args!("{} {}", self.rmailbx, self.emailbx))
+    }
+}
+
+impl Encodable for MINFOData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(encoder.emit_name(&self.rmailbx));
+        encoder.emit_name(&self.emailbx)
+    }
+}
+
+impl Decodable for MINFOData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<MINFOData> {
+        let rmailbx = try!(decoder.read_name());
+        let emailbx = try!(decoder.read_name());
+        Ok(MINFOData::new(rmailbx, emailbx))
+    }
+}
+
+/// RDATA of RP records (RFC 1183 2.2): the mailbox of the responsible
+/// person and a name with TXT records about them.
+#[derive(Debug,PartialEq)]
+pub struct RPData {
+    pub mbox: Name,
+    pub txt: Name,
+}
+
+impl RPData {
+    pub fn new(mbox: Name, txt: Name) -> RPData {
+        RPData {
+            mbox: mbox,
+            txt: txt,
+        }
+    }
+}
+
+impl Display for RPData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_fmt(format_args!("{} {}", self.mbox, self.txt))
+    }
+}
+
+impl Encodable for RPData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(encoder.emit_name_uncompressed(&self.mbox));
+        encoder.emit_name_uncompressed(&self.txt)
+    }
+}
+
+impl Decodable for RPData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<RPData> {
+        let mbox = try!(decoder.read_name());
+        let txt = try!(decoder.read_name());
+        Ok(RPData::new(mbox, txt))
+    }
+}
+
+/// RDATA of AFSDB records (RFC 1183 1): an AFS or DCE server for a cell.
+#[derive(Debug,PartialEq)]
+pub struct AFSDBData {
+    pub subtype: u16,
+    pub hostname: Name,
+}
+
+impl AFSDBData {
+    pub fn new(subtype: u16, hostname: Name) -> AFSDBData {
+        AFSDBData {
+            subtype: subtype,
+            hostname: hostname,
+        }
+    }
+}
+
+impl Display for AFSDBData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_fmt(format_args!("{} {}", self.subtype, self.hostname))
+    }
+}
+
+impl Encodable for AFSDBData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(encoder.emit_u16(self.subtype));
+        encoder.emit_name_uncompressed(&self.hostname)
+    }
+}
+
+impl Decodable for AFSDBData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<AFSDBData> {
+        let subtype = try!(decoder.read_u16());
+        let hostname = try!(decoder.read_name());
+        Ok(AFSDBData::new(subtype, hostname))
+    }
+}
+
+/// RDATA of X25 records (RFC 1183 3.1): a PSDN address.
+#[derive(Debug,PartialEq)]
+pub struct X25Data {
+    pub address: Vec<u8>,
+}
+
+impl X25Data {
+    pub fn new(address: Vec<u8>) -> X25Data {
+        X25Data {
+            address: address,
+        }
+    }
+}
+
+impl Display for X25Data {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_str(&quote(&self.address))
+    }
+}
+
+impl Encodable for X25Data {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        encoder.emit_character_string(&self.address)
+    }
+}
+
+impl Decodable for X25Data {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<X25Data> {
+        Ok(X25Data::new(try!(decoder.read_character_string())))
+    }
+}
+
+/// RDATA of ISDN records (RFC 1183 3.2): an ISDN number and an optional
+/// subaddress.
+#[derive(Debug,PartialEq)]
+pub struct ISDNData {
+    pub address: Vec<u8>,
+    pub subaddress: Option<Vec<u8>>,
+}
+
+impl ISDNData {
+    pub fn new(address: Vec<u8>, subaddress: Option<Vec<u8>>) -> ISDNData {
+        ISDNData {
+            address: address,
+            subaddress: subaddress,
+        }
+    }
+}
+
+impl Display for ISDNData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        try!(fmt.write_str(&quote(&self.address)));
+        if let Some(ref subaddress) = self.subaddress {
+            try!(fmt.write_fmt(format_args!(" {}", quote(subaddress))));
+        }
+        Ok(())
+    }
+}
+
+impl Encodable for ISDNData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(encoder.emit_character_string(&self.address));
+        match self.subaddress {
+            Some(ref subaddress) => encoder.emit_character_string(subaddress),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Decodable for ISDNData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<ISDNData> {
+        let address = try!(decoder.read_character_string());
+        let subaddress = if decoder.remaining() > 0 {
+            Some(try!(decoder.read_character_string()))
+        } else {
+            None
+        };
+        Ok(ISDNData::new(address, subaddress))
+    }
+}
+
+/// RDATA of RT records (RFC 1183 3.3): an intermediate host to route
+/// through, like MX for hosts without direct connectivity.
+#[derive(Debug,PartialEq)]
+pub struct RTData {
+    pub preference: u16,
+    pub host: Name,
+}
+
+impl RTData {
+    pub fn new(preference: u16, host: Name) -> RTData {
+        RTData {
+            preference: preference,
+            host: host,
+        }
+    }
+}
+
+impl Display for RTData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_fmt(format_args!("{} {}", self.preference, self.host))
+    }
+}
+
+impl Encodable for RTData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(encoder.emit_u16(self.preference));
+        encoder.emit_name_uncompressed(&self.host)
+    }
+}
+
+impl Decodable for RTData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<RTData> {
+        let preference = try!(decoder.read_u16());
+        let host = try!(decoder.read_name());
+        Ok(RTData::new(preference, host))
+    }
+}
+
+/// RDATA of WKS records (RFC 1035 3.4.2): the services offered on an
+/// address, with the port bitmap expanded into a list of ports.
+#[derive(Debug,PartialEq)]
+pub struct WKSData {
+    pub address: Ipv4Addr,
+    pub protocol: u8,
+    pub ports: Vec<u16>,
+}
+
+impl WKSData {
+    pub fn new(address: Ipv4Addr, protocol: u8, ports: Vec<u16>) -> WKSData {
+        WKSData {
+            address: address,
+            protocol: protocol,
+            ports: ports,
+        }
+    }
+}
+
+impl Display for WKSData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        try!(fmt.write_fmt(format_args!("{}", self.address)));
+        match protocol_name(self.protocol) {
+            Some(name) => try!(fmt.write_fmt(format_args!(" {}", name))),
+            None => try!(fmt.write_fmt(format_args!(" {}", self.protocol))),
+        }
+        for port in &self.ports {
+            match service_name(*port) {
+                Some(name) => try!(fmt.write_fmt(format_args!(" {}", name))),
+                None => try!(fmt.write_fmt(format_args!(" {}", port))),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Encodable for WKSData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(encoder.emit_u32(u32::from(self.address)));
+        try!(encoder.emit_u8(self.protocol));
+        let length = match self.ports.iter().max() {
+            Some(&max) => max as usize / 8 + 1,
+            None => 0,
+        };
+        let mut bitmap = vec![0u8; length];
+        for &port in &self.ports {
+            bitmap[port as usize / 8] |= 0x80 >> (port % 8);
+        }
+        encoder.emit_bytes(&bitmap)
+    }
+}
+
+impl Decodable for WKSData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<WKSData> {
+        let start = decoder.position();
+        let address = Ipv4Addr::from(try!(decoder.read_u32()));
+        let protocol = try!(decoder.read_u8());
+        let length = decoder.remaining();
+        // one bit for each of the 65536 ports
+        if length > 8192 {
+            return Err(DecodeError::BadRdLength { offset: start, length: length + 5 });
+        }
+        let bitmap = try!(decoder.read_bytes(length));
+        let mut ports = Vec::new();
+        for (i, byte) in bitmap.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    ports.push((i * 8 + bit) as u16);
+                }
+            }
+        }
+        Ok(WKSData::new(address, protocol, ports))
+    }
+}
+
+/// Mnemonics for the protocols WKS records are used with.
+fn protocol_name(protocol: u8) -> Option<&'static str> {
+    match protocol {
+        6  => Some("tcp"),
+        17 => Some("udp"),
+        _  => None,
+    }
+}
+
+/// Names of well-known services, as in /etc/services.
+fn service_name(port: u16) -> Option<&'static str> {
+    match port {
+        7   => Some("echo"),
+        9   => Some("discard"),
+        13  => Some("daytime"),
+        21  => Some("ftp"),
+        22  => Some("ssh"),
+        23  => Some("telnet"),
+        25  => Some("smtp"),
+        37  => Some("time"),
+        43  => Some("whois"),
+        53  => Some("domain"),
+        69  => Some("tftp"),
+        70  => Some("gopher"),
+        79  => Some("finger"),
+        80  => Some("http"),
+        110 => Some("pop3"),
+        111 => Some("sunrpc"),
+        119 => Some("nntp"),
+        123 => Some("ntp"),
+        143 => Some("imap"),
+        161 => Some("snmp"),
+        389 => Some("ldap"),
+        443 => Some("https"),
+        _   => None,
+    }
+}
+
+/// RDATA of SRV records (RFC 2782).
+#[derive(Debug,PartialEq)]
+pub struct SRVData {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: Name,
+}
+
+impl SRVData {
+    pub fn new(priority: u16, weight: u16, port: u16, target: Name) -> SRVData {
+        SRVData {
+            priority: priority,
+            weight: weight,
+            port: port,
+            target: target,
+        }
+    }
+}
+
+impl Display for SRVData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_fmt(format_args!("{} {} {} {}", self.priority, self.weight, self.port, self.target))
+    }
+}
+
+impl Encodable for SRVData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(encoder.emit_u16(self.priority));
+        try!(encoder.emit_u16(self.weight));
+        try!(encoder.emit_u16(self.port));
+        // RFC 2782 forbids compressing the target
+        encoder.emit_name_uncompressed(&self.target)
+    }
+}
+
+impl Decodable for SRVData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<SRVData> {
+        let priority = try!(decoder.read_u16());
+        let weight = try!(decoder.read_u16());
+        let port = try!(decoder.read_u16());
+        let target = try!(decoder.read_name());
+        Ok(SRVData::new(priority, weight, port, target))
+    }
+}
+
+/// RDATA of NAPTR records (RFC 3403 4.1).
+#[derive(Debug,PartialEq)]
+pub struct NAPTRData {
+    pub order: u16,
+    pub preference: u16,
+    pub flags: Vec<u8>,
+    pub services: Vec<u8>,
+    pub regexp: Vec<u8>,
+    pub replacement: Name,
+}
+
+impl NAPTRData {
+    pub fn new(order: u16, preference: u16,
+               flags: Vec<u8>, services: Vec<u8>, regexp: Vec<u8>,
+               replacement: Name) -> NAPTRData {
+        NAPTRData {
+            order: order,
+            preference: preference,
+            flags: flags,
+            services: services,
+            regexp: regexp,
+            replacement: replacement,
+        }
+    }
+}
+
+impl Display for NAPTRData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_fmt(format_args!("{} {} {} {} {} {}",
+            self.order,
+            self.preference,
+            quote(&self.flags),
+            quote(&self.services),
+            quote(&self.regexp),
+            self.replacement
+        ))
+    }
+}
+
+impl Encodable for NAPTRData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(encoder.emit_u16(self.order));
+        try!(encoder.emit_u16(self.preference));
+        try!(encoder.emit_character_string(&self.flags));
+        try!(encoder.emit_character_string(&self.services));
+        try!(encoder.emit_character_string(&self.regexp));
+        encoder.emit_name_uncompressed(&self.replacement)
+    }
+}
+
+impl Decodable for NAPTRData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<NAPTRData> {
+        let order = try!(decoder.read_u16());
+        let preference = try!(decoder.read_u16());
+        let flags = try!(decoder.read_character_string());
+        let services = try!(decoder.read_character_string());
+        let regexp = try!(decoder.read_character_string());
+        let replacement = try!(decoder.read_name());
+        Ok(NAPTRData::new(order, preference, flags, services, regexp, replacement))
+    }
+}
+
+/// RDATA of TXT and SPF records: one or more character-strings, which may
+/// hold arbitrary octets.
+#[derive(Debug,PartialEq)]
+pub struct TXTData {
+    pub strings: Vec<Vec<u8>>,
+}
+
+impl TXTData {
+    pub fn new(strings: Vec<Vec<u8>>) -> TXTData {
+        TXTData {
+            strings: strings,
+        }
+    }
+}
+
+impl Display for TXTData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let strings: Vec<String> = self.strings.iter().map(|s| quote(s)).collect();
+        fmt.write_str(&strings.join(" "))
+    }
+}
+
+impl Encodable for TXTData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        if self.strings.is_empty() {
+            return Err(EncodeError::EmptyTxt);
+        }
+        for string in &self.strings {
+            try!(encoder.emit_character_string(string));
+        }
+        Ok(())
+    }
+}
+
+impl Decodable for TXTData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<TXTData> {
+        if decoder.remaining() == 0 {
+            return Err(DecodeError::BadRdLength { offset: decoder.position(), length: 0 });
+        }
+        let mut strings = Vec::new();
+        while decoder.remaining() > 0 {
+            strings.push(try!(decoder.read_character_string()));
+        }
+        Ok(TXTData::new(strings))
+    }
+}
+
+/// Presents a character-string in double quotes, escaping `"` and `\` with
+/// a backslash and octets that are not printable ASCII as `\DDD`.
+fn quote(v: &[u8]) -> String {
+    let mut s = String::from("\"");
+    for &b in v {
+        match b {
+            b'"' | b'\\' => {
+                s.push('\\');
+                s.push(b as char);
+            },
+            b if b >= 0x20 && b <= 0x7e => s.push(b as char),
+            _ => s.push_str(&format!("\\{:03}", b)),
+        }
+    }
+    s.push('"');
+    s
+}
+
+/// Property tag of a CAA record. Tags are matched case-insensitively.
+#[derive(Debug,PartialEq)]
+pub enum CAATag {
+    Issue,
+    IssueWild,
+    Iodef,
+    Unknown(Vec<u8>),
+}
+
+impl CAATag {
+    pub fn from_bytes(v: &[u8]) -> CAATag {
+        let lower: Vec<u8> = v.iter().map(|b| b.to_ascii_lowercase()).collect();
+        match &lower[..] {
+            b"issue"     => CAATag::Issue,
+            b"issuewild" => CAATag::IssueWild,
+            b"iodef"     => CAATag::Iodef,
+            _            => CAATag::Unknown(v.to_vec()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> &[u8] {
+        match *self {
+            CAATag::Issue          => b"issue",
+            CAATag::IssueWild      => b"issuewild",
+            CAATag::Iodef          => b"iodef",
+            CAATag::Unknown(ref v) => v,
+        }
+    }
+}
+
+impl Display for CAATag {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_str(&String::from_utf8_lossy(self.to_bytes()))
+    }
+}
+
+/// RDATA of CAA records (RFC 8659).
+#[derive(Debug,PartialEq)]
+pub struct CAAData {
+    pub flags: u8,
+    /// The property tag as written, so that it encodes back unchanged.
+    pub tag: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+impl CAAData {
+    pub fn new(flags: u8, tag: CAATag, value: Vec<u8>) -> CAAData {
+        CAAData {
+            flags: flags,
+            tag: tag.to_bytes().to_vec(),
+            value: value,
+        }
+    }
+
+    pub fn property(&self) -> CAATag {
+        CAATag::from_bytes(&self.tag)
+    }
+
+    /// Whether the issuer critical flag is set, in which case a CA that
+    /// does not understand the tag must not issue.
+    pub fn is_critical(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+
+    /// The issuer domain of an `issue` or `issuewild` property, without its
+    /// parameters. An empty value, which allows no CA at all, gives `Some("")`.
+    pub fn issuer(&self) -> Option<String> {
+        match self.property() {
+            CAATag::Issue | CAATag::IssueWild => {
+                let value = String::from_utf8_lossy(&self.value);
+                let domain = value.split(';').next().unwrap_or("");
+                Some(domain.trim().to_string())
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Display for CAAData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_fmt(format_args!("{} {} {}", self.flags, String::from_utf8_lossy(&self.tag), quote(&self.value)))
+    }
+}
+
+impl Encodable for CAAData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        if !is_caa_tag(&self.tag) {
+            return Err(EncodeError::BadCaaTag { tag: String::from_utf8_lossy(&self.tag).into_owned() });
+        }
+        try!(encoder.emit_u8(self.flags));
+        try!(encoder.emit_character_string(&self.tag));
+        encoder.emit_bytes(&self.value)
+    }
+}
+
+impl Decodable for CAAData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<CAAData> {
+        let flags = try!(decoder.read_u8());
+        let offset = decoder.position();
+        let tag = try!(decoder.read_character_string());
+        if !is_caa_tag(&tag) {
+            return Err(DecodeError::BadCaaTag { offset: offset });
+        }
+        let length = decoder.remaining();
+        let value = try!(decoder.read_bytes(length));
+        Ok(CAAData {
+            flags: flags,
+            tag: tag,
+            value: value,
+        })
+    }
+}
+
+/// Whether `tag` is a valid property tag: one or more ASCII letters and
+/// digits (RFC 8659 4.1).
+fn is_caa_tag(tag: &[u8]) -> bool {
+    !tag.is_empty() && tag.iter().all(|b| b.is_ascii_alphanumeric())
+}
+
+/// A single EDNS option of an OPT record.
+#[derive(Debug,PartialEq)]
+pub struct EdnsOption {
+    pub code: u16,
+    pub data: Vec<u8>,
+}
+
+impl EdnsOption {
+    pub fn new(code: u16, data: Vec<u8>) -> EdnsOption {
+        EdnsOption {
+            code: code,
+            data: data,
+        }
+    }
+}
+
+impl Display for EdnsOption {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        try!(fmt.write_fmt(format_args!("{}:", self.code)));
+        for b in &self.data {
+            try!(fmt.write_fmt(format_args!("{:02x}", b)));
+        }
+        Ok(())
+    }
+}
+
+impl Encodable for EdnsOption {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        try!(encoder.emit_u16(self.code));
+        try!(encoder.emit_u16(self.data.len() as u16));
+        encoder.emit_bytes(&self.data)
+    }
+}
+
+impl Decodable for EdnsOption {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<EdnsOption> {
+        let code = try!(decoder.read_u16());
+        let length = try!(decoder.read_u16()) as usize;
+        let data = try!(decoder.read_bytes(length));
+        Ok(EdnsOption::new(code, data))
+    }
+}
+
+/// RDATA of the OPT pseudo-record. Its class holds the UDP payload size and
+/// its TTL the extended RCODE, version and flags, so only the options are
+/// kept here.
+#[derive(Debug,PartialEq)]
+pub struct OPTData {
+    pub options: Vec<EdnsOption>,
+}
+
+impl OPTData {
+    pub fn new(options: Vec<EdnsOption>) -> OPTData {
+        OPTData {
+            options: options,
+        }
+    }
+}
+
+impl Display for OPTData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let options: Vec<String> = self.options.iter().map(|o| o.to_string()).collect();
+        fmt.write_str(&options.join(" "))
+    }
+}
+
+impl Encodable for OPTData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        encoder.emit_vec(&self.options)
+    }
+}
+
+impl Decodable for OPTData {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<OPTData> {
+        let mut options = Vec::new();
+        while decoder.remaining() > 0 {
+            options.push(try!(EdnsOption::decode(decoder)));
+        }
+        Ok(OPTData::new(options))
+    }
+}
+
 #[derive(Debug,PartialEq)]
 pub enum RData {
     A(Ipv4Addr),
-    NS(String),
-    CNAME(String),
+    NS(Name),
+    CNAME(Name),
     AAAA(Ipv6Addr),
     SOA(SOAData),
+    WKS(WKSData),
     MX(MXData),
-    PTR(String),
+    PTR(Name),
+    HINFO(HINFOData),
+    MINFO(MINFOData),
+    TXT(TXTData),
+    RP(RPData),
+    AFSDB(AFSDBData),
+    X25(X25Data),
+    ISDN(ISDNData),
+    RT(RTData),
+    SPF(TXTData),
+    SRV(SRVData),
+    NAPTR(NAPTRData),
+    OPT(OPTData),
+    CAA(CAAData),
+    /// RDATA of a type that is not decoded, kept as it was on the wire.
+    Unknown { rtype: u16, data: Vec<u8> },
 }
 
 impl Display for RData {
@@ -95,64 +955,600 @@ impl Display for RData {
             RData::CNAME(ref cname) => fmt.write_fmt(format_args!("{}", cname)),
             RData::AAAA(ipv6) => fmt.write_fmt(format_args!("{}", ipv6)),
             RData::SOA(ref soa) => fmt.write_fmt(format_args!("{}", soa)),
+            RData::WKS(ref wks) => fmt.write_fmt(format_args!("{}", wks)),
             RData::MX(ref mx) => fmt.write_fmt(format_args!("{}", mx)),
             RData::PTR(ref ptr) => fmt.write_fmt(format_args!("{}", ptr)),
+            RData::HINFO(ref hinfo) => fmt.write_fmt(format_args!("{}", hinfo)),
+            RData::MINFO(ref minfo) => fmt.write_fmt(format_args!("{}", minfo)),
+            RData::TXT(ref txt) => fmt.write_fmt(format_args!("{}", txt)),
+            RData::RP(ref rp) => fmt.write_fmt(format_args!("{}", rp)),
+            RData::AFSDB(ref afsdb) => fmt.write_fmt(format_args!("{}", afsdb)),
+            RData::X25(ref x25) => fmt.write_fmt(format_args!("{}", x25)),
+            RData::ISDN(ref isdn) => fmt.write_fmt(format_args!("{}", isdn)),
+            RData::RT(ref rt) => fmt.write_fmt(format_args!("{}", rt)),
+            RData::SPF(ref spf) => fmt.write_fmt(format_args!("{}", spf)),
+            RData::SRV(ref srv) => fmt.write_fmt(format_args!("{}", srv)),
+            RData::NAPTR(ref naptr) => fmt.write_fmt(format_args!("{}", naptr)),
+            RData::OPT(ref opt) => fmt.write_fmt(format_args!("{}", opt)),
+            RData::CAA(ref caa) => fmt.write_fmt(format_args!("{}", caa)),
+            RData::Unknown { ref data, .. } => {
+                // RFC 3597 generic RDATA presentation
+                try!(fmt.write_fmt(format_args!("\\# {}", data.len())));
+                if !data.is_empty() {
+                    try!(fmt.write_str(" "));
+                }
+                for b in data {
+                    try!(fmt.write_fmt(format_args!("{:02x}", b)));
+                }
+                Ok(())
+            },
         }
     }
 }
 
+impl Encodable for RData {
+    fn encode(&self, encoder: &mut Encoder) -> EncodeResult<()> {
+        match *self {
+            RData::A(ip) => encoder.emit_u32(u32::from(ip)),
+            RData::NS(ref ns) => encoder.emit_name(ns),
+            RData::CNAME(ref cname) => encoder.emit_name(cname),
+            RData::AAAA(ipv6) => {
+                for segment in ipv6.segments().iter() {
+                    try!(encoder.emit_u16(*segment));
+                }
+                Ok(())
+            },
+            RData::SOA(ref soa) => soa.encode(encoder),
+            RData::WKS(ref wks) => wks.encode(encoder),
+            RData::MX(ref mx) => mx.encode(encoder),
+            RData::PTR(ref ptr) => encoder.emit_name(ptr),
+            RData::HINFO(ref hinfo) => hinfo.encode(encoder),
+            RData::MINFO(ref minfo) => minfo.encode(encoder),
+            RData::TXT(ref txt) => txt.encode(encoder),
+            RData::RP(ref rp) => rp.encode(encoder),
+            RData::AFSDB(ref afsdb) => afsdb.encode(encoder),
+            RData::X25(ref x25) => x25.encode(encoder),
+            RData::ISDN(ref isdn) => isdn.encode(encoder),
+            RData::RT(ref rt) => rt.encode(encoder),
+            RData::SPF(ref spf) => spf.encode(encoder),
+            RData::SRV(ref srv) => srv.encode(encoder),
+            RData::NAPTR(ref naptr) => naptr.encode(encoder),
+            RData::OPT(ref opt) => opt.encode(encoder),
+            RData::CAA(ref caa) => caa.encode(encoder),
+            RData::Unknown { ref data, .. } => encoder.emit_bytes(data),
+        }
+    }
+}
+
+impl RData {
+    /// Decodes RDATA of the given type. The decoder must be bounded to the
+    /// RDLENGTH, since `Unknown` takes whatever is left.
+    pub fn decode(rtype: ResourceType, decoder: &mut Decoder) -> DecodeResult<RData> {
+        let rdata = match rtype {
+            ResourceType::A => RData::A(Ipv4Addr::from(try!(decoder.read_u32()))),
+            ResourceType::NS => RData::NS(try!(decoder.read_name())),
+            ResourceType::CNAME => RData::CNAME(try!(decoder.read_name())),
+            ResourceType::AAAA => {
+                let mut segments = [0u16; 8];
+                for segment in segments.iter_mut() {
+                    *segment = try!(decoder.read_u16());
+                }
+                RData::AAAA(Ipv6Addr::new(
+                    segments[0], segments[1], segments[2], segments[3],
+                    segments[4], segments[5], segments[6], segments[7],
+                ))
+            },
+            ResourceType::SOA => RData::SOA(try!(SOAData::decode(decoder))),
+            ResourceType::WKS => try!(or_unknown(rtype, decoder, |d| WKSData::decode(d).map(RData::WKS))),
+            ResourceType::MX => RData::MX(try!(MXData::decode(decoder))),
+            ResourceType::PTR => RData::PTR(try!(decoder.read_name())),
+            ResourceType::HINFO => RData::HINFO(try!(HINFOData::decode(decoder))),
+            ResourceType::MINFO => RData::MINFO(try!(MINFOData::decode(decoder))),
+            ResourceType::TXT => try!(or_unknown(rtype, decoder, |d| TXTData::decode(d).map(RData::TXT))),
+            ResourceType::RP => RData::RP(try!(RPData::decode(decoder))),
+            ResourceType::AFSDB => RData::AFSDB(try!(AFSDBData::decode(decoder))),
+            ResourceType::X25 => RData::X25(try!(X25Data::decode(decoder))),
+            ResourceType::ISDN => RData::ISDN(try!(ISDNData::decode(decoder))),
+            ResourceType::RT => RData::RT(try!(RTData::decode(decoder))),
+            ResourceType::SPF => try!(or_unknown(rtype, decoder, |d| TXTData::decode(d).map(RData::SPF))),
+            ResourceType::SRV => RData::SRV(try!(SRVData::decode(decoder))),
+            ResourceType::NAPTR => RData::NAPTR(try!(NAPTRData::decode(decoder))),
+            ResourceType::OPT => RData::OPT(try!(OPTData::decode(decoder))),
+            ResourceType::CAA => try!(or_unknown(rtype, decoder, |d| CAAData::decode(d).map(RData::CAA))),
+            ty => {
+                let length = decoder.remaining();
+                RData::Unknown { rtype: ty.to_u16(), data: try!(decoder.read_bytes(length)) }
+            },
+        };
+        Ok(rdata)
+    }
+}
+
+/// Decodes RDATA with `f`, but keeps it as `RData::Unknown` when it is
+/// complete yet invalid for its type, so that one such record does not fail
+/// the whole message.
+fn or_unknown<F>(rtype: ResourceType, decoder: &mut Decoder, f: F) -> DecodeResult<RData>
+    where F: FnOnce(&mut Decoder) -> DecodeResult<RData> {
+    let start = decoder.position();
+    match f(decoder) {
+        Err(DecodeError::BadRdLength { .. }) | Err(DecodeError::BadCaaTag { .. }) => {
+            decoder.seek(start);
+            let length = decoder.remaining();
+            Ok(RData::Unknown { rtype: rtype.to_u16(), data: try!(decoder.read_bytes(length)) })
+        },
+        result => result,
+    }
+}
+
 #[derive(Copy,Clone,Debug,PartialEq)]
 pub enum ResourceType {
-    A     = 1,
-    NS    = 2,
-    CNAME = 5,
-    SOA   = 6,
-    WKS   = 11,
-    PTR   = 12,
-    MX    = 15,
-    AAAA  = 28,
-    SRV   = 33,
+    A,
+    NS,
+    CNAME,
+    SOA,
+    WKS,
+    PTR,
+    HINFO,
+    MINFO,
+    MX,
+    TXT,
+    RP,
+    AFSDB,
+    X25,
+    ISDN,
+    RT,
+    AAAA,
+    SRV,
+    NAPTR,
+    SPF,
+    /// EDNS pseudo-record (RFC 6891).
+    OPT,
+    CAA,
+    /// QTYPE `*`, asking for records of every type.
+    ANY,
+    /// Any other type, by number. Known numbers are always decoded to their
+    /// named variant, so `Unknown(1)` is never produced for `A`.
+    Unknown(u16),
 }
 
 impl FromPrimitive for ResourceType {
     fn from_i64(n: i64) -> Option<Self> {
         match n {
-            1  => Some(ResourceType::A),
-            2  => Some(ResourceType::NS),
-            5  => Some(ResourceType::CNAME),
-            6  => Some(ResourceType::SOA),
-            11 => Some(ResourceType::WKS),
-            12 => Some(ResourceType::PTR),
-            15 => Some(ResourceType::MX),
-            28 => Some(ResourceType::AAAA),
-            33 => Some(ResourceType::SRV),
-            _  => None,
+            1   => Some(ResourceType::A),
+            2   => Some(ResourceType::NS),
+            5   => Some(ResourceType::CNAME),
+            6   => Some(ResourceType::SOA),
+            11  => Some(ResourceType::WKS),
+            12  => Some(ResourceType::PTR),
+            13  => Some(ResourceType::HINFO),
+            14  => Some(ResourceType::MINFO),
+            15  => Some(ResourceType::MX),
+            16  => Some(ResourceType::TXT),
+            17  => Some(ResourceType::RP),
+            18  => Some(ResourceType::AFSDB),
+            19  => Some(ResourceType::X25),
+            20  => Some(ResourceType::ISDN),
+            21  => Some(ResourceType::RT),
+            28  => Some(ResourceType::AAAA),
+            33  => Some(ResourceType::SRV),
+            35  => Some(ResourceType::NAPTR),
+            41  => Some(ResourceType::OPT),
+            99  => Some(ResourceType::SPF),
+            255 => Some(ResourceType::ANY),
+            257 => Some(ResourceType::CAA),
+            n if n >= 0 && n <= 0xffff => Some(ResourceType::Unknown(n as u16)),
+            _   => None,
         }
     }
     fn from_u64(n: u64) -> Option<Self> {
+        if n > 0xffff {
+            return None;
+        }
         ResourceType::from_i64(n as i64)
     }
 }
 
 impl ResourceType {
+    /// Parses a type mnemonic, ignoring case.
     pub fn from_string(v: String) -> Option<ResourceType> {
-        match v.as_ref() {
+        let upper = v.to_uppercase();
+        match upper.as_ref() {
             "A"     => Some(ResourceType::A),
             "NS"    => Some(ResourceType::NS),
             "CNAME" => Some(ResourceType::CNAME),
             "SOA"   => Some(ResourceType::SOA),
             "WKS"   => Some(ResourceType::WKS),
             "PTR"   => Some(ResourceType::PTR),
+            "HINFO" => Some(ResourceType::HINFO),
+            "MINFO" => Some(ResourceType::MINFO),
             "MX"    => Some(ResourceType::MX),
+            "TXT"   => Some(ResourceType::TXT),
+            "RP"    => Some(ResourceType::RP),
+            "AFSDB" => Some(ResourceType::AFSDB),
+            "X25"   => Some(ResourceType::X25),
+            "ISDN"  => Some(ResourceType::ISDN),
+            "RT"    => Some(ResourceType::RT),
             "SRV"   => Some(ResourceType::SRV),
+            "NAPTR" => Some(ResourceType::NAPTR),
+            "SPF"   => Some(ResourceType::SPF),
             "AAAA"  => Some(ResourceType::AAAA),
-            _       => None,
+            "OPT"   => Some(ResourceType::OPT),
+            "CAA"   => Some(ResourceType::CAA),
+            "ANY"   => Some(ResourceType::ANY),
+            _       => ResourceType::from_generic(&upper),
+        }
+    }
+
+    /// Parses the RFC 3597 `TYPEnnn` mnemonic from an uppercased `v`.
+    fn from_generic(v: &str) -> Option<ResourceType> {
+        if !v.starts_with("TYPE") || !is_decimal(&v[4..]) {
+            return None;
+        }
+        match v[4..].parse::<u16>() {
+            Ok(n) => ResourceType::from_u16(n),
+            Err(_) => None,
+        }
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        match *self {
+            ResourceType::A          => 1,
+            ResourceType::NS         => 2,
+            ResourceType::CNAME      => 5,
+            ResourceType::SOA        => 6,
+            ResourceType::WKS        => 11,
+            ResourceType::PTR        => 12,
+            ResourceType::HINFO      => 13,
+            ResourceType::MINFO      => 14,
+            ResourceType::MX         => 15,
+            ResourceType::TXT        => 16,
+            ResourceType::RP         => 17,
+            ResourceType::AFSDB      => 18,
+            ResourceType::X25        => 19,
+            ResourceType::ISDN       => 20,
+            ResourceType::RT         => 21,
+            ResourceType::AAAA       => 28,
+            ResourceType::SRV        => 33,
+            ResourceType::NAPTR      => 35,
+            ResourceType::OPT        => 41,
+            ResourceType::SPF        => 99,
+            ResourceType::ANY        => 255,
+            ResourceType::CAA        => 257,
+            ResourceType::Unknown(n) => n,
+        }
+    }
+}
+
+impl Decodable for ResourceType {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<ResourceType> {
+        let n = try!(decoder.read_u16());
+        Ok(ResourceType::from_u16(n).unwrap_or(ResourceType::Unknown(n)))
+    }
+}
+
+impl Display for ResourceType {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match *self {
+            ResourceType::Unknown(n) => fmt.write_fmt(format_args!("TYPE{}", n)),
+            ty => fmt.write_fmt(format_args!("{:?}", ty)),
         }
     }
 }
 
 
-#[derive(Clone,Debug,PartialEq)]
+#[derive(Copy,Clone,Debug,PartialEq)]
 pub enum ResourceClass {
-    IN = 1,
+    IN,
+    CH,
+    HS,
+    /// Only meaningful in dynamic update prerequisites (RFC 2136).
+    NONE,
+    /// QCLASS `*`, matching any class.
+    ANY,
+    /// Any other class, by number. As with `ResourceType::Unknown`, known
+    /// numbers are always decoded to their named variant.
+    Unknown(u16),
+}
+
+impl FromPrimitive for ResourceClass {
+    fn from_i64(n: i64) -> Option<Self> {
+        match n {
+            1   => Some(ResourceClass::IN),
+            3   => Some(ResourceClass::CH),
+            4   => Some(ResourceClass::HS),
+            254 => Some(ResourceClass::NONE),
+            255 => Some(ResourceClass::ANY),
+            n if n >= 0 && n <= 0xffff => Some(ResourceClass::Unknown(n as u16)),
+            _   => None,
+        }
+    }
+    fn from_u64(n: u64) -> Option<Self> {
+        if n > 0xffff {
+            return None;
+        }
+        ResourceClass::from_i64(n as i64)
+    }
+}
+
+impl ResourceClass {
+    /// Parses a class mnemonic, ignoring case.
+    pub fn from_string(v: String) -> Option<ResourceClass> {
+        let upper = v.to_uppercase();
+        match upper.as_ref() {
+            "IN"   => Some(ResourceClass::IN),
+            "CH"   => Some(ResourceClass::CH),
+            "HS"   => Some(ResourceClass::HS),
+            "NONE" => Some(ResourceClass::NONE),
+            "ANY"  => Some(ResourceClass::ANY),
+            _      => ResourceClass::from_generic(&upper),
+        }
+    }
+
+    /// Parses the RFC 3597 `CLASSnnn` mnemonic from an uppercased `v`.
+    fn from_generic(v: &str) -> Option<ResourceClass> {
+        if !v.starts_with("CLASS") || !is_decimal(&v[5..]) {
+            return None;
+        }
+        match v[5..].parse::<u16>() {
+            Ok(n) => ResourceClass::from_u16(n),
+            Err(_) => None,
+        }
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        match *self {
+            ResourceClass::IN         => 1,
+            ResourceClass::CH         => 3,
+            ResourceClass::HS         => 4,
+            ResourceClass::NONE       => 254,
+            ResourceClass::ANY        => 255,
+            ResourceClass::Unknown(n) => n,
+        }
+    }
+}
+
+/// Whether `v` is a non-empty run of ASCII digits, which unlike `parse`
+/// rules out a leading sign.
+fn is_decimal(v: &str) -> bool {
+    !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit())
+}
+
+impl Decodable for ResourceClass {
+    fn decode(decoder: &mut Decoder) -> DecodeResult<ResourceClass> {
+        let n = try!(decoder.read_u16());
+        Ok(ResourceClass::from_u16(n).unwrap_or(ResourceClass::Unknown(n)))
+    }
+}
+
+impl Display for ResourceClass {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match *self {
+            ResourceClass::Unknown(n) => fmt.write_fmt(format_args!("CLASS{}", n)),
+            class => fmt.write_fmt(format_args!("{:?}", class)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Resource, ResourceType, ResourceClass, RData, TXTData, WKSData, CAAData, CAATag};
+    use super::{HINFOData, ISDNData, RTData, NAPTRData};
+    use name::Name;
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+    use binary::encoder::{self, EncodeError};
+    use binary::decoder::{self, Decoder, DecodeError};
+    use num::FromPrimitive;
+
+    #[test]
+    fn test_resource_type_from_string() {
+        assert_eq!(ResourceType::from_string("MX".to_string()), Some(ResourceType::MX));
+        assert_eq!(ResourceType::from_string("TYPE65534".to_string()), Some(ResourceType::Unknown(65534)));
+        assert_eq!(ResourceType::from_string("type98".to_string()), Some(ResourceType::Unknown(98)));
+        assert_eq!(ResourceType::from_string("TYPE15".to_string()), Some(ResourceType::MX));
+        assert_eq!(ResourceType::from_string("TYPE65536".to_string()), None);
+        assert_eq!(ResourceType::from_string("TYPE".to_string()), None);
+        assert_eq!(ResourceType::from_string("BOGUS".to_string()), None);
+        assert_eq!(ResourceType::from_string("mx".to_string()), Some(ResourceType::MX));
+        assert_eq!(ResourceType::from_string("Txt".to_string()), Some(ResourceType::TXT));
+        assert_eq!(ResourceType::from_string("TYPE+15".to_string()), None);
+        assert_eq!(ResourceType::from_string("ANY".to_string()), Some(ResourceType::ANY));
+        assert_eq!(ResourceType::from_string("TYPE255".to_string()), Some(ResourceType::ANY));
+        assert_eq!(ResourceType::ANY.to_u16(), 255);
+        assert_eq!(ResourceType::from_string("TYPE-1".to_string()), None);
+    }
+
+    #[test]
+    fn test_resource_type_numbers() {
+        assert_eq!(ResourceType::from_u16(28), Some(ResourceType::AAAA));
+        assert_eq!(ResourceType::from_u16(65534), Some(ResourceType::Unknown(65534)));
+        assert_eq!(ResourceType::from_i64(70000), None);
+        assert_eq!(ResourceType::AAAA.to_u16(), 28);
+        assert_eq!(ResourceType::Unknown(65534).to_u16(), 65534);
+    }
+
+    #[test]
+    fn test_resource_type_display() {
+        assert_eq!(format!("{}", ResourceType::SRV), "SRV");
+        assert_eq!(format!("{}", ResourceType::Unknown(65534)), "TYPE65534");
+    }
+
+    #[test]
+    fn test_resource_class_from_string() {
+        assert_eq!(ResourceClass::from_string("CH".to_string()), Some(ResourceClass::CH));
+        assert_eq!(ResourceClass::from_string("NONE".to_string()), Some(ResourceClass::NONE));
+        assert_eq!(ResourceClass::from_string("CLASS255".to_string()), Some(ResourceClass::ANY));
+        assert_eq!(ResourceClass::from_string("CLASS32769".to_string()), Some(ResourceClass::Unknown(32769)));
+        assert_eq!(ResourceClass::from_string("TXT".to_string()), None);
+        assert_eq!(ResourceClass::from_string("ch".to_string()), Some(ResourceClass::CH));
+        assert_eq!(ResourceClass::from_string("class3".to_string()), Some(ResourceClass::CH));
+        assert_eq!(ResourceClass::from_string("CLASS+3".to_string()), None);
+    }
+
+    #[test]
+    fn test_resource_class_numbers() {
+        assert_eq!(ResourceClass::from_u16(3), Some(ResourceClass::CH));
+        assert_eq!(ResourceClass::from_u16(254), Some(ResourceClass::NONE));
+        assert_eq!(ResourceClass::from_u16(1232), Some(ResourceClass::Unknown(1232)));
+        assert_eq!(ResourceClass::HS.to_u16(), 4);
+        assert_eq!(ResourceClass::ANY.to_u16(), 255);
+        assert_eq!(format!("{}", ResourceClass::CH), "CH");
+        assert_eq!(format!("{}", ResourceClass::Unknown(1232)), "CLASS1232");
+    }
+
+    #[test]
+    fn test_txt() {
+        let txt = TXTData::new(vec![b"v=spf1 -all".to_vec(), b"say \"hi\" \\o/".to_vec(), vec![0, 0xe4], vec![]]);
+        assert_eq!(format!("{}", RData::TXT(txt)), "\"v=spf1 -all\" \"say \\\"hi\\\" \\\\o/\" \"\\000\\228\" \"\"");
+
+        let encoded = [3u8, 97, 98, 99, 0, 1, 0xff];
+        let mut decoder = Decoder::new(&encoded);
+        let rdata = RData::decode(ResourceType::SPF, &mut decoder);
+        assert_eq!(rdata, Ok(RData::SPF(TXTData::new(vec![b"abc".to_vec(), vec![], vec![0xff]]))));
+        assert_eq!(encoder::encode(&rdata.unwrap()), Ok(encoded.to_vec()));
+
+        let mut decoder = Decoder::new(&[3u8, 97]);
+        assert_eq!(RData::decode(ResourceType::TXT, &mut decoder), Err(DecodeError::Truncated { offset: 1, needed: 3 }));
+
+        // at least one character-string is required
+        assert_eq!(encoder::encode(&TXTData::new(vec![])), Err(EncodeError::EmptyTxt));
+        let txt: Result<TXTData, _> = decoder::decode(&[]);
+        assert_eq!(txt, Err(DecodeError::BadRdLength { offset: 0, length: 0 }));
+        let mut decoder = Decoder::new(&[]);
+        assert_eq!(RData::decode(ResourceType::TXT, &mut decoder), Ok(RData::Unknown { rtype: 16, data: vec![] }));
+        assert_eq!(ResourceType::from_string("TXT".to_string()), Some(ResourceType::TXT));
+        assert_eq!(ResourceType::from_u16(99), Some(ResourceType::SPF));
+    }
+
+    #[test]
+    fn test_wks() {
+        // 192.0.2.1 tcp with ports 21, 25, 80 and 1234
+        let mut encoded = vec![192u8, 0, 2, 1, 6, 0x00, 0x00, 0x04, 0x40];
+        encoded.extend(vec![0u8; 6]);
+        encoded.push(0x80);
+        encoded.extend(vec![0u8; 143]);
+        encoded.push(0x20);
+        let mut decoder = Decoder::new(&encoded);
+        let rdata = RData::decode(ResourceType::WKS, &mut decoder);
+        let expected = WKSData::new(Ipv4Addr::new(192, 0, 2, 1), 6, vec![21, 25, 80, 1234]);
+        assert_eq!(rdata, Ok(RData::WKS(expected)));
+        let rdata = rdata.unwrap();
+        assert_eq!(format!("{}", rdata), "192.0.2.1 tcp ftp smtp http 1234");
+        assert_eq!(encoder::encode(&rdata), Ok(encoded));
+
+        let wks = WKSData::new(Ipv4Addr::new(192, 0, 2, 1), 47, vec![]);
+        assert_eq!(format!("{}", wks), "192.0.2.1 47");
+        assert_eq!(encoder::encode(&wks), Ok(vec![192, 0, 2, 1, 47]));
+
+        let mut decoder = Decoder::new(&[192u8, 0, 2, 1]);
+        assert_eq!(RData::decode(ResourceType::WKS, &mut decoder), Err(DecodeError::Truncated { offset: 4, needed: 1 }));
+
+        // a bitmap past port 65535 is kept as it is
+        let mut encoded = vec![192u8, 0, 2, 1, 6];
+        encoded.extend(vec![0xffu8; 8193]);
+        let wks: Result<WKSData, _> = decoder::decode(&encoded);
+        assert_eq!(wks, Err(DecodeError::BadRdLength { offset: 0, length: 8198 }));
+        let mut decoder = Decoder::new(&encoded);
+        let rdata = RData::decode(ResourceType::WKS, &mut decoder).unwrap();
+        assert_eq!(decoder.remaining(), 0);
+        assert_eq!(encoder::encode(&rdata), Ok(encoded.clone()));
+        assert_eq!(rdata, RData::Unknown { rtype: 11, data: encoded });
+    }
+
+    #[test]
+    fn test_caa() {
+        let encoded = b"\x80\x05issueca.example.net; account=230123".to_vec();
+        let mut decoder = Decoder::new(&encoded);
+        let caa = match RData::decode(ResourceType::CAA, &mut decoder) {
+            Ok(RData::CAA(caa)) => caa,
+            other => panic!("unexpected result {:?}", other),
+        };
+        assert!(caa.is_critical());
+        assert_eq!(caa.property(), CAATag::Issue);
+        assert_eq!(caa.issuer(), Some("ca.example.net".to_string()));
+        assert_eq!(format!("{}", caa), "128 issue \"ca.example.net; account=230123\"");
+        assert_eq!(encoder::encode(&caa), Ok(encoded));
+
+        assert_eq!(CAATag::from_bytes(b"IssueWild"), CAATag::IssueWild);
+        // the tag keeps its case through decode and encode
+        let encoded = b"\x00\x09IssueWild;".to_vec();
+        let caa: CAAData = decoder::decode(&encoded).unwrap();
+        assert_eq!(caa.property(), CAATag::IssueWild);
+        assert_eq!(format!("{}", caa), "0 IssueWild \";\"");
+        assert_eq!(encoder::encode(&caa), Ok(encoded));
+        assert_eq!(CAATag::from_bytes(b"iodef"), CAATag::Iodef);
+        assert_eq!(CAATag::from_bytes(b"tbs"), CAATag::Unknown(b"tbs".to_vec()));
+
+        let wild = CAAData::new(0, CAATag::IssueWild, b";".to_vec());
+        assert_eq!(wild.issuer(), Some("".to_string()));
+        let iodef = CAAData::new(0, CAATag::Iodef, b"mailto:security@example.com".to_vec());
+        assert!(!iodef.is_critical());
+        assert_eq!(iodef.issuer(), None);
+        assert_eq!(format!("{}", iodef), "0 iodef \"mailto:security@example.com\"");
+        let unknown = CAAData::new(0, CAATag::Unknown(b"tbs".to_vec()), b"Unknown".to_vec());
+        assert_eq!(encoder::encode(&unknown), Ok(b"\x00\x03tbsUnknown".to_vec()));
+
+        // empty tags and tags with anything but letters and digits
+        let decoded: Result<CAAData, _> = decoder::decode(b"\x00\x00;");
+        assert_eq!(decoded, Err(DecodeError::BadCaaTag { offset: 1 }));
+        let decoded: Result<CAAData, _> = decoder::decode(b"\x00\x05is-ue;");
+        assert_eq!(decoded, Err(DecodeError::BadCaaTag { offset: 1 }));
+        // but such a record does not fail the message it is in
+        let mut decoder = Decoder::new(b"\x00\x05is-ue;");
+        let rdata = RData::decode(ResourceType::CAA, &mut decoder);
+        assert_eq!(rdata, Ok(RData::Unknown { rtype: 257, data: b"\x00\x05is-ue;".to_vec() }));
+        let record: Result<Resource, _> = decoder::decode(b"\x00\x01\x01\x00\x01\x00\x00\x00\x3c\x00\x08\x00\x05is-ue;");
+        assert_eq!(record.map(|r| r.rdata), Ok(RData::Unknown { rtype: 257, data: b"\x00\x05is-ue;".to_vec() }));
+        let bad = CAAData::new(0, CAATag::Unknown(b"is sue".to_vec()), Vec::new());
+        assert_eq!(encoder::encode(&bad), Err(EncodeError::BadCaaTag { tag: "is sue".to_string() }));
+        assert_eq!(ResourceType::from_string("CAA".to_string()), Some(ResourceType::CAA));
+        assert_eq!(ResourceType::CAA.to_u16(), 257);
+    }
+
+    #[test]
+    fn test_legacy_types() {
+        let hinfo = RData::HINFO(HINFOData::new(b"DEC-2060".to_vec(), b"TOPS20".to_vec()));
+        assert_eq!(format!("{}", hinfo), "\"DEC-2060\" \"TOPS20\"");
+        let isdn = RData::ISDN(ISDNData::new(b"150862028003217".to_vec(), Some(b"004".to_vec())));
+        assert_eq!(format!("{}", isdn), "\"150862028003217\" \"004\"");
+
+        // the RT host is never compressed, but compressed names are accepted
+        let host = Name::from_str("relay.prime.com").unwrap();
+        let rt = RData::RT(RTData::new(2, host));
+        let encoded = encoder::encode(&rt).unwrap();
+        assert_eq!(encoded.len(), 2 + 17);
+        assert_eq!(format!("{}", rt), "2 relay.prime.com");
+        let data = [5u8, 112, 114, 105, 109, 101, 0, 0, 10, 5, 114, 101, 108, 97, 121, 0xc0, 0];
+        let mut decoder = Decoder::new(&data);
+        decoder.seek(7);
+        match RData::decode(ResourceType::RT, &mut decoder) {
+            Ok(RData::RT(rt)) => assert_eq!(rt.host.to_string(), "relay.prime."),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        for &(name, number) in [("HINFO", 13u16), ("MINFO", 14), ("RP", 17), ("AFSDB", 18), ("X25", 19), ("ISDN", 20), ("RT", 21)].iter() {
+            let rtype = ResourceType::from_string(name.to_string()).unwrap();
+            assert_eq!(rtype.to_u16(), number);
+            assert_eq!(ResourceType::from_u16(number), Some(rtype));
+            assert_eq!(format!("{}", rtype), name);
+        }
+    }
+
+    #[test]
+    fn test_naptr() {
+        let replacement = Name::from_str(".").unwrap();
+        let naptr = NAPTRData::new(100, 10, b"u".to_vec(), b"E2U+sip".to_vec(),
+                                   b"!^.*$!sip:info@example.com!".to_vec(), replacement);
+        assert_eq!(format!("{}", naptr), "100 10 \"u\" \"E2U+sip\" \"!^.*$!sip:info@example.com!\" .");
+
+        let encoded = encoder::encode(&naptr).unwrap();
+        assert_eq!(&encoded[..7], &[0u8, 100, 0, 10, 1, b'u', 7]);
+        let mut decoder = Decoder::new(&encoded);
+        assert_eq!(RData::decode(ResourceType::NAPTR, &mut decoder), Ok(RData::NAPTR(naptr)));
+
+        let mut decoder = Decoder::new(&encoded[..encoded.len() - 1]);
+        assert!(RData::decode(ResourceType::NAPTR, &mut decoder).is_err());
+        assert_eq!(ResourceType::from_string("NAPTR".to_string()), Some(ResourceType::NAPTR));
+        assert_eq!(ResourceType::from_u16(35), Some(ResourceType::NAPTR));
+    }
 }